@@ -1,5 +1,6 @@
 mod color;
 
+use std::env;
 use std::fs;
 use configparser::ini::Ini;
 use nn::NN;
@@ -16,6 +17,30 @@ fn cost(ic1: &[f64; 3], ic2: &[f64; 3], ii: f64, oc: &[f64; 3]) -> f64 {
     (de1 + de2) * ((oi - ii).abs() + 0.5)
 }
 
+fn seed_setting(config: &Ini, args: &[String]) -> u64 {
+    let seed = match args.iter().position(|arg| arg == "--seed") {
+        Some(idx) => Some(args.get(idx + 1).expect("\"--seed\" must be followed by a seed.").clone()),
+        None => config.get("general", "seed"),
+    };
+
+    match seed.as_deref() {
+        None | Some("random") => rand::thread_rng().gen(),
+        Some(seed) => seed.parse().expect("The seed must be a non-negative integer or \"random\"."),
+    }
+}
+
+/// Loads a network written by nn. The gradients are drawn from sRGB outputs, so networks trained on colors in another
+/// encoding are rejected. Networks written before nn stored the encoding were trained on sRGB colors.
+fn load_nn(path: &str) -> NN {
+    let json = fs::read_to_string(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+    let network: serde_json::Value = serde_json::from_str(&json).unwrap_or_else(|_| panic!("The file \"{}\" isn't a valid network.", path));
+    let color_encoding = network.get("color_encoding").and_then(|encoding| encoding.as_str()).unwrap_or("srgb");
+    if color_encoding != "srgb" {
+        panic!("The network in \"{}\" was trained on \"{}\" colors but \"srgb\" colors are needed.", path, color_encoding);
+    }
+    NN::from_json(&json)
+}
+
 fn main() -> Result<(), String> {
     // ---------------------------------------------------------------------------------------------
     // Setup
@@ -25,6 +50,8 @@ fn main() -> Result<(), String> {
     let mut config = Ini::new();
     let _ = config.read(config_str);
 
+    let args = env::args().collect::<Vec<_>>();
+
     let use_sample_nn = config.getbool("general", "use_sample_nn").unwrap().unwrap();
     let image_width = config.getint("images", "width").unwrap().unwrap() as i32;
     let image_height = config.getint("images", "height").unwrap().unwrap() as i32;
@@ -35,18 +62,12 @@ fn main() -> Result<(), String> {
     let min_width = config.getint("quality", "min_width").unwrap().unwrap() as i32;
 
     let nn = if use_sample_nn {
-        NN::from_json(
-            &fs::read_to_string("sample_nn.json")
-                .expect("Couldn't read the file \"sample_nn.json\"")
-        )
+        load_nn("sample_nn.json")
     } else {
-        NN::from_json(
-            &fs::read_to_string("nn.json")
-                .expect("Couldn't read the file \"nn.json\"")
-        )
+        load_nn("nn.json")
     };
 
-    let seed = rand::thread_rng().gen();
+    let seed = seed_setting(&config, &args);
     println!("Seed: {}", seed);
     let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
 
@@ -0,0 +1,49 @@
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::split::Splitting;
+use crate::train::{State, Training};
+
+/// Where the samples come from and how they are held out, which must not change when resuming.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Data {
+    pub data_file: String,
+    /// SHA-256 of the content of `data_file`, so that a changed dataset isn't mistaken for the original one.
+    pub data_sha256: String,
+    pub color_encoding: String,
+    pub validation_file: Option<String>,
+    pub validation_sample_count: usize,
+    pub test_file: Option<String>,
+    pub test_sample_count: usize,
+    pub splitting: Splitting,
+}
+
+/// Everything needed to continue an interrupted training run exactly where the checkpoint was saved.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub layers: Vec<u32>,
+    pub data: Data,
+    pub training: Training,
+    pub state: State,
+}
+
+impl Checkpoint {
+    pub fn path(seed: u64) -> String {
+        format!("nn_{}.checkpoint.json", seed)
+    }
+
+    pub fn load(seed: u64) -> Checkpoint {
+        let path = Checkpoint::path(seed);
+        let json = fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        serde_json::from_str(&json).expect("Found an invalid checkpoint.")
+    }
+
+    /// Written to a temporary file first so that an interruption never leaves a truncated checkpoint.
+    pub fn save(&self) {
+        let path = Checkpoint::path(self.seed);
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, serde_json::to_string(self).unwrap()).unwrap();
+        fs::rename(&tmp_path, &path).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::evaluation::Evaluation;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveFormat {
+    None,
+    Csv,
+    Jsonl,
+}
+
+impl FromStr for CurveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(CurveFormat::None),
+            "csv" => Ok(CurveFormat::Csv),
+            "jsonl" => Ok(CurveFormat::Jsonl),
+            _ => Err(format!("Unknown curve format \"{}\".", s)),
+        }
+    }
+}
+
+/// One epoch of the training curve. The validation columns are only set in the epochs the validation samples are
+/// evaluated.
+#[derive(Serialize)]
+pub struct Point {
+    pub epoch: u32,
+    pub elapsed_seconds: f64,
+    pub learning_rate: f64,
+    pub training_loss: f64,
+    pub validation_loss: Option<f64>,
+    pub validation_mse: Option<f64>,
+    pub validation_delta_e_mean: Option<f64>,
+    pub validation_delta_e_median: Option<f64>,
+    pub validation_delta_e_p95: Option<f64>,
+    pub validation_delta_e_p99: Option<f64>,
+    pub validation_delta_e_max: Option<f64>,
+    pub validation_under_1: Option<f64>,
+    pub validation_under_2: Option<f64>,
+}
+
+const COLUMNS: [&str; 13] = [
+    "epoch", "elapsed_seconds", "learning_rate", "training_loss", "validation_loss", "validation_mse",
+    "validation_delta_e_mean", "validation_delta_e_median", "validation_delta_e_p95", "validation_delta_e_p99",
+    "validation_delta_e_max", "validation_under_1", "validation_under_2",
+];
+
+impl Point {
+    pub fn new(epoch: u32, elapsed_seconds: f64, learning_rate: f64, training_loss: f64) -> Point {
+        Point {
+            epoch,
+            elapsed_seconds,
+            learning_rate,
+            training_loss,
+            validation_loss: None,
+            validation_mse: None,
+            validation_delta_e_mean: None,
+            validation_delta_e_median: None,
+            validation_delta_e_p95: None,
+            validation_delta_e_p99: None,
+            validation_delta_e_max: None,
+            validation_under_1: None,
+            validation_under_2: None,
+        }
+    }
+
+    pub fn set_validation(&mut self, loss: f64, mse: f64, evaluation: &Evaluation) {
+        self.validation_loss = Some(loss);
+        self.validation_mse = Some(mse);
+        self.validation_delta_e_mean = Some(evaluation.mean);
+        self.validation_delta_e_median = Some(evaluation.median);
+        self.validation_delta_e_p95 = Some(evaluation.p95);
+        self.validation_delta_e_p99 = Some(evaluation.p99);
+        self.validation_delta_e_max = Some(evaluation.max);
+        self.validation_under_1 = Some(evaluation.under_1);
+        self.validation_under_2 = Some(evaluation.under_2);
+    }
+
+    fn csv_row(&self) -> String {
+        let optional = |v: Option<f64>| v.map_or(String::new(), |v| v.to_string());
+        [
+            self.epoch.to_string(),
+            self.elapsed_seconds.to_string(),
+            self.learning_rate.to_string(),
+            self.training_loss.to_string(),
+            optional(self.validation_loss),
+            optional(self.validation_mse),
+            optional(self.validation_delta_e_mean),
+            optional(self.validation_delta_e_median),
+            optional(self.validation_delta_e_p95),
+            optional(self.validation_delta_e_p99),
+            optional(self.validation_delta_e_max),
+            optional(self.validation_under_1),
+            optional(self.validation_under_2),
+        ].join(", ")
+    }
+}
+
+/// The per-epoch training curve, written to "nn_[seed].curve.csv" or "nn_[seed].curve.jsonl".
+pub struct Curve {
+    format: CurveFormat,
+    file: File,
+}
+
+impl Curve {
+    pub fn path(seed: u64, format: CurveFormat) -> String {
+        match format {
+            CurveFormat::Jsonl => format!("nn_{}.curve.jsonl", seed),
+            _ => format!("nn_{}.curve.csv", seed),
+        }
+    }
+
+    /// When resuming after `resume_epoch` epochs, the points of later epochs, written before the run was interrupted,
+    /// are dropped so that the curve matches an uninterrupted run.
+    pub fn open(seed: u64, format: CurveFormat, resume_epoch: Option<u32>) -> Option<Curve> {
+        if format == CurveFormat::None {
+            return None;
+        }
+        let path = Curve::path(seed, format);
+
+        let kept = match resume_epoch {
+            Some(resume_epoch) => fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .filter(|line| match format {
+                    CurveFormat::Csv => line.split(", ").next().and_then(|epoch| epoch.parse::<u32>().ok()).is_some_and(|epoch| epoch <= resume_epoch),
+                    _ => serde_json::from_str::<serde_json::Value>(line)
+                        .ok()
+                        .and_then(|point| point["epoch"].as_u64())
+                        .is_some_and(|epoch| epoch <= resume_epoch as u64),
+                })
+                .map(|line| format!("{}\n", line))
+                .collect::<String>(),
+            None => String::new(),
+        };
+
+        let mut file = OpenOptions::new().create(true).write(true).truncate(true).open(&path).unwrap();
+        if format == CurveFormat::Csv {
+            writeln!(file, "{}", COLUMNS.join(", ")).unwrap();
+        }
+        file.write_all(kept.as_bytes()).unwrap();
+        Some(Curve { format, file })
+    }
+
+    pub fn write(&mut self, point: &Point) {
+        let line = match self.format {
+            CurveFormat::Jsonl => serde_json::to_string(point).unwrap(),
+            _ => point.csv_row(),
+        };
+        writeln!(self.file, "{}", line).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use memmap2::Mmap;
+use crate::color::*;
+use crate::metadata;
+
+/// See the binary dataset format of sample-generator.
+const MAGIC: &[u8; 4] = b"CGDS";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+
+/// Inputs and outputs of the network per sample.
+pub const INPUT_COUNT: usize = 7;
+pub const OUTPUT_COUNT: usize = 3;
+const SAMPLE_LEN: usize = INPUT_COUNT + OUTPUT_COUNT;
+
+/// The inputs followed by the outputs of every sample, stored contiguously.
+#[derive(Clone, Default)]
+pub struct Samples {
+    values: Vec<f64>,
+}
+
+impl Samples {
+    pub fn with_capacity(count: usize) -> Samples {
+        Samples { values: Vec::with_capacity(count * SAMPLE_LEN) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() / SAMPLE_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn inputs(&self, idx: usize) -> &[f64] {
+        &self.values[idx * SAMPLE_LEN..idx * SAMPLE_LEN + INPUT_COUNT]
+    }
+
+    pub fn targets(&self, idx: usize) -> &[f64] {
+        &self.values[idx * SAMPLE_LEN + INPUT_COUNT..(idx + 1) * SAMPLE_LEN]
+    }
+
+    /// The inputs and the outputs of every sample.
+    pub fn iter(&self) -> impl Iterator<Item = (&[f64], &[f64])> {
+        self.values.chunks_exact(SAMPLE_LEN).map(|sample| sample.split_at(INPUT_COUNT))
+    }
+
+    /// Samples from the inputs followed by the outputs of every sample.
+    #[cfg(test)]
+    pub fn from_values(values: Vec<f64>) -> Samples {
+        assert!(values.len().is_multiple_of(SAMPLE_LEN));
+        Samples { values }
+    }
+
+    /// Appends sample `idx` of `other`.
+    pub fn push_from(&mut self, other: &Samples, idx: usize) {
+        self.values.extend_from_slice(&other.values[idx * SAMPLE_LEN..(idx + 1) * SAMPLE_LEN]);
+    }
+}
+
+/// Positions of the network's inputs and outputs in the rows of a dataset.
+struct Columns {
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    lab: bool,
+}
+
+impl Columns {
+    /// Datasets can contain the colors in several encodings, only the columns of `color_encoding` are used.
+    fn new(names: &[String], color_encoding: &str) -> Columns {
+        let channels = match color_encoding {
+            "srgb" => ["r", "g", "b"],
+            "linear_rgb" => ["lin_r", "lin_g", "lin_b"],
+            "lab" => ["lab_l", "lab_a", "lab_b"],
+            _ => panic!("Unknown color encoding \"{}\".", color_encoding),
+        };
+        let column = |name: String| names
+            .iter()
+            .position(|n| *n == name)
+            .unwrap_or_else(|| panic!("The dataset has no \"{}\" column.", name));
+        let color = |prefix: &str| channels.iter().map(|channel| column(format!("{}_{}", prefix, channel))).collect::<Vec<_>>();
+
+        let mut inputs = color("c1");
+        inputs.extend(color("c2"));
+        inputs.push(column("t".to_string()));
+
+        Columns {
+            inputs,
+            outputs: color("out"),
+            lab: color_encoding == "lab",
+        }
+    }
+
+    /// Appends the sample in a row whose value in column `idx` is `value(idx)`.
+    fn push(&self, value: impl Fn(usize) -> f64, samples: &mut Samples) {
+        let scale = |idx: usize, k: usize| {
+            let v = value(idx);
+            if self.lab && k < 3 {
+                (v - CIELAB_MIN[k]) / (CIELAB_MAX[k] - CIELAB_MIN[k])
+            } else {
+                v
+            }
+        };
+
+        samples.values.extend(self.inputs.iter().enumerate().map(|(k, &idx)| scale(idx, if k < 6 { k % 3 } else { 3 })));
+        samples.values.extend(self.outputs.iter().enumerate().map(|(k, &idx)| scale(idx, k)));
+    }
+}
+
+/// The CIELAB color of three inputs or outputs of the network, undoing the scaling of CIELAB columns.
+pub fn cielab(color: &[f64], color_encoding: &str) -> [f64; 3] {
+    let c = [color[0], color[1], color[2]];
+    match color_encoding {
+        "srgb" => rgb_to_cielab(&srgb_to_rgb(&c)),
+        "linear_rgb" => rgb_to_cielab(&c),
+        "lab" => [0, 1, 2].map(|k| CIELAB_MIN[k] + c[k] * (CIELAB_MAX[k] - CIELAB_MIN[k])),
+        _ => panic!("Unknown color encoding \"{}\".", color_encoding),
+    }
+}
+
+/// Loads the samples of a CSV or, if the file name ends with ".bin", a binary dataset.
+pub fn load(path: &str, color_encoding: &str) -> Samples {
+    if path.ends_with(".bin") {
+        load_binary(path, color_encoding)
+    } else {
+        load_csv(path, color_encoding)
+    }
+}
+
+/// Column names of datasets without a CSV header, from their metadata or, for datasets written before the color encoding
+/// was configurable, from the column count.
+fn stored_names(path: &str, columns: usize) -> Result<Vec<String>, String> {
+    if let Some(names) = metadata::columns(path) {
+        return Ok(names);
+    }
+
+    let names = ["c1_r", "c1_g", "c1_b", "c2_r", "c2_g", "c2_b", "t", "out_r", "out_g", "out_b"];
+    match columns {
+        10 => Ok(names.iter().map(|name| name.to_string()).collect()),
+        // Sequence datasets start every row with the id of the gradient it belongs to.
+        11 => Ok(["pair_id"].iter().chain(names.iter()).map(|name| name.to_string()).collect()),
+        _ => Err(format!("The columns of \"{}\" are unknown.", path)),
+    }
+}
+
+fn load_csv(path: &str, color_encoding: &str) -> Samples {
+    let buffered = BufReader::new(File::open(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path)));
+
+    let mut columns = None;
+    let mut data = Samples::default();
+    for line in buffered.lines().map_while(Result::ok) {
+        if line.is_empty() {
+            continue;
+        }
+        if columns.is_none() && line.split(", ").any(|s| s.parse::<f64>().is_err()) {
+            let names = line.split(", ").map(String::from).collect::<Vec<_>>();
+            columns = Some((names.len(), Columns::new(&names, color_encoding)));
+            continue;
+        }
+
+        let nums = line.split(", ").map(|s| s.parse::<f64>().expect("Found an invalid sample.")).collect::<Vec<_>>();
+        let (count, columns) = columns.get_or_insert_with(|| (nums.len(), Columns::new(&stored_names(path, nums.len()).unwrap_or_else(|error| panic!("{}", error)), color_encoding)));
+        if nums.len() != *count {
+            panic!("Found an invalid sample.");
+        }
+        columns.push(|idx| nums[idx], &mut data);
+    }
+    data
+}
+
+/// The samples are read straight from the memory-mapped rows, without reading the whole file first.
+fn load_binary(path: &str, color_encoding: &str) -> Samples {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+    let bytes = unsafe { Mmap::map(&file) }.unwrap();
+
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+        panic!("Found an invalid binary dataset header.");
+    }
+    let field = |idx: usize| u32::from_le_bytes(bytes[idx * 4..idx * 4 + 4].try_into().unwrap()) as usize;
+    if field(1) != VERSION as usize {
+        panic!("Unsupported binary dataset version {}.", field(1));
+    }
+    let value_len = field(2);
+    let columns = field(3);
+    if (value_len != 4 && value_len != 8) || columns == 0 {
+        panic!("Found an invalid binary dataset header.");
+    }
+
+    let rows = bytes[HEADER_LEN..].chunks_exact(value_len * columns);
+    if !rows.remainder().is_empty() {
+        panic!("The file \"{}\" ends with an incomplete sample.", path);
+    }
+    let names = stored_names(path, columns).unwrap_or_else(|error| panic!("{}", error));
+    if names.len() != columns {
+        panic!("The dataset \"{}\" has {} columns but its metadata lists {}.", path, columns, names.len());
+    }
+    let columns = Columns::new(&names, color_encoding);
+
+    let mut data = Samples::with_capacity(rows.len());
+    for row in rows {
+        let value = |idx: usize| {
+            let v = &row[idx * value_len..(idx + 1) * value_len];
+            match value_len {
+                4 => f32::from_le_bytes(v.try_into().unwrap()) as f64,
+                _ => f64::from_le_bytes(v.try_into().unwrap()),
+            }
+        };
+        columns.push(value, &mut data);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use super::*;
+
+    const ROWS: [[f64; 10]; 2] = [
+        [0.1, 0.2, 0.3, 0.9, 0.8, 0.7, 0.25, 0.4, 0.45, 0.5],
+        [0.9, 0.8, 0.7, 0.1, 0.2, 0.3, 1.0 / 3.0, 0.6, 0.55, 0.5],
+    ];
+
+    fn write_binary(path: &str, value_len: u32, columns: u32) {
+        let mut bytes = MAGIC.to_vec();
+        for field in [VERSION, value_len, columns] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        for v in ROWS.iter().flatten() {
+            match value_len {
+                4 => bytes.extend_from_slice(&(*v as f32).to_le_bytes()),
+                _ => bytes.extend_from_slice(&v.to_le_bytes()),
+            }
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn binary_datasets_load_like_csv_datasets() {
+        let dir = env::temp_dir();
+        let csv_path = dir.join("nn_roundtrip.csv").to_string_lossy().to_string();
+        let csv = ROWS.iter().map(|row| row.map(|v| v.to_string()).join(", ") + "\n").collect::<String>();
+        fs::write(&csv_path, csv).unwrap();
+        let expected = load(&csv_path, "srgb");
+        fs::remove_file(&csv_path).unwrap();
+
+        for value_len in [4, 8] {
+            let path = dir.join(format!("nn_roundtrip_{}.bin", value_len)).to_string_lossy().to_string();
+            write_binary(&path, value_len, 10);
+            let data = load(&path, "srgb");
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(data.len(), expected.len());
+            for ((inputs, targets), (expected_inputs, expected_targets)) in data.iter().zip(expected.iter()) {
+                let expected = expected_inputs.iter().chain(expected_targets.iter());
+                for (v, expected) in inputs.iter().chain(targets.iter()).zip(expected) {
+                    match value_len {
+                        4 => assert_eq!(*v, *expected as f32 as f64),
+                        _ => assert_eq!(v, expected),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid binary dataset header")]
+    fn binary_headers_need_columns() {
+        let path = env::temp_dir().join("nn_no_columns.bin").to_string_lossy().to_string();
+        write_binary(&path, 8, 0);
+        load(&path, "srgb");
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use crate::color::cielab_dist_ciede2000;
+use crate::dataset::{self, Samples};
+use crate::mlp::Mlp;
+
+/// Statistics of the CIEDE2000 distances between the predicted and the target colors of samples.
+#[derive(Serialize, Deserialize)]
+pub struct Evaluation {
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    /// Fractions of the samples with a distance below 1 and 2.
+    pub under_1: f64,
+    pub under_2: f64,
+}
+
+impl Evaluation {
+    pub fn new(mlp: &Mlp, data: &Samples, color_encoding: &str) -> Evaluation {
+        let mut distances = data
+            .iter()
+            .map(|(inputs, targets)| {
+                let results = mlp.run(inputs);
+                cielab_dist_ciede2000(&dataset::cielab(&results, color_encoding), &dataset::cielab(targets, color_encoding))
+            })
+            .collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.total_cmp(b));
+
+        let count = distances.len().max(1) as f64;
+        // Nearest-rank percentiles.
+        let percentile = |p: f64| distances.get(((p * distances.len() as f64).ceil() as usize).saturating_sub(1)).copied().unwrap_or(0.0);
+        let fraction_under = |limit: f64| distances.iter().filter(|&&d| d < limit).count() as f64 / count;
+
+        Evaluation {
+            mean: distances.iter().sum::<f64>() / count,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: distances.last().copied().unwrap_or(0.0),
+            under_1: fraction_under(1.0),
+            under_2: fraction_under(2.0),
+        }
+    }
+
+    pub fn print(&self, name: &str) {
+        println!(
+            "{} CIEDE2000 error: mean {:.3}, median {:.3}, 95th percentile {:.3}, 99th percentile {:.3}, max {:.3}",
+            name, self.mean, self.median, self.p95, self.p99, self.max,
+        );
+        println!("{} samples under 1: {:.1}%, under 2: {:.1}%", name, 100.0 * self.under_1, 100.0 * self.under_2);
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use crate::color::cielab_dist_ciede2000;
+use crate::dataset;
+
+/// Step of the central differences of the CIEDE2000 loss, small compared to the outputs' range of [0, 1].
+const STEP: f64 = 1e-5;
+
+/// What training minimises for every sample.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Loss {
+    /// Mean squared error of the output channels.
+    Mse,
+    /// Squared CIEDE2000 distance between the output and the target color, with the outputs given in `color_encoding`.
+    DeltaE { color_encoding: String },
+}
+
+impl Loss {
+    pub fn new(name: &str, color_encoding: &str) -> Loss {
+        match name {
+            "mse" => Loss::Mse,
+            "delta_e" => Loss::DeltaE { color_encoding: color_encoding.to_string() },
+            _ => panic!("Unknown loss \"{}\".", name),
+        }
+    }
+
+    pub fn error(&self, outputs: &[f64], targets: &[f64]) -> f64 {
+        match self {
+            Loss::Mse => outputs.iter().zip(targets.iter()).map(|(r, t)| (t - r).powi(2)).sum::<f64>() / outputs.len() as f64,
+            Loss::DeltaE { color_encoding } => {
+                let distance = cielab_dist_ciede2000(&dataset::cielab(outputs, color_encoding), &dataset::cielab(targets, color_encoding));
+                distance.powi(2)
+            },
+        }
+    }
+
+    /// The error of one sample and its derivatives with respect to the outputs. CIEDE2000 has no convenient closed-form
+    /// derivative, so it is differentiated through the conversion to CIELAB with central differences.
+    pub fn derivatives(&self, outputs: &[f64], targets: &[f64]) -> (f64, Vec<f64>) {
+        let error = self.error(outputs, targets);
+        let derivatives = match self {
+            Loss::Mse => outputs.iter().zip(targets.iter()).map(|(r, t)| -2.0 * (t - r) / outputs.len() as f64).collect(),
+            Loss::DeltaE { .. } => {
+                let mut shifted = outputs.to_vec();
+                (0..outputs.len())
+                    .map(|idx| {
+                        shifted[idx] = outputs[idx] + STEP;
+                        let above = self.error(&shifted, targets);
+                        shifted[idx] = outputs[idx] - STEP;
+                        let below = self.error(&shifted, targets);
+                        shifted[idx] = outputs[idx];
+                        (above - below) / (2.0 * STEP)
+                    })
+                    .collect()
+            },
+        };
+        (error, derivatives)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    /// Central differences of the error with a larger step than `STEP`.
+    fn numeric_derivatives(loss: &Loss, outputs: &[f64], targets: &[f64]) -> Vec<f64> {
+        let step = 1e-4;
+        (0..outputs.len())
+            .map(|idx| {
+                let mut shifted = outputs.to_vec();
+                shifted[idx] += step;
+                let above = loss.error(&shifted, targets);
+                shifted[idx] -= 2.0 * step;
+                (above - loss.error(&shifted, targets)) / (2.0 * step)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mse_derivatives_are_analytic() {
+        let (outputs, targets) = ([0.2, 0.5, 0.9], [0.4, 0.5, 0.3]);
+        let (error, derivatives) = Loss::Mse.derivatives(&outputs, &targets);
+
+        assert!((error - (0.04 + 0.36) / 3.0).abs() < 1e-12);
+        for (derivative, expected) in derivatives.iter().zip([-0.4 / 3.0, 0.0, 1.2 / 3.0]) {
+            assert!((derivative - expected).abs() < 1e-12);
+        }
+        for (derivative, numeric) in derivatives.iter().zip(numeric_derivatives(&Loss::Mse, &outputs, &targets)) {
+            assert!((derivative - numeric).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn delta_e_derivatives_match_finite_differences() {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(4);
+        for color_encoding in ["srgb", "linear_rgb", "lab"] {
+            let loss = Loss::new("delta_e", color_encoding);
+            for _ in 0..20 {
+                let outputs = (0..3).map(|_| rng.gen_range(0.1..0.9)).collect::<Vec<_>>();
+                let targets = (0..3).map(|_| rng.gen_range(0.1..0.9)).collect::<Vec<_>>();
+                let (error, derivatives) = loss.derivatives(&outputs, &targets);
+
+                assert_eq!(error, loss.error(&outputs, &targets));
+                for (derivative, numeric) in derivatives.iter().zip(numeric_derivatives(&loss, &outputs, &targets)) {
+                    assert!((derivative - numeric).abs() <= 1e-3 * numeric.abs().max(1.0), "{} vs {}", derivative, numeric);
+                }
+            }
+        }
+    }
+}
@@ -1,82 +1,215 @@
+use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::{Write, BufRead, BufReader};
+use std::io::Write;
 use std::time::Instant;
 use configparser::ini::Ini;
-use nn::{NN, HaltCondition};
+use rand::{Rng, SeedableRng};
+use crate::checkpoint::{Checkpoint, Data};
+use crate::curve::{Curve, CurveFormat};
+use crate::evaluation::Evaluation;
+use crate::loss::Loss;
+use crate::mlp::Mlp;
+use crate::settings::{check, layer_sizes, setting};
+use crate::split::Splitting;
+use crate::sweep::Summary;
+use crate::train::{State, Training};
+
+mod checkpoint;
+mod color;
+mod curve;
+mod dataset;
+mod evaluation;
+mod loss;
+mod metadata;
+mod mlp;
+mod optimizer;
+mod settings;
+mod split;
+mod sweep;
+mod train;
+
+fn seed_setting(config: &Ini, args: &[String]) -> u64 {
+    let seed = match args.iter().position(|arg| arg == "--seed") {
+        Some(idx) => Some(args.get(idx + 1).expect("\"--seed\" must be followed by a seed.").clone()),
+        None => config.get("general", "seed"),
+    };
+
+    match seed.as_deref() {
+        None | Some("random") => rand::thread_rng().gen(),
+        Some(seed) => seed.parse().expect("The seed must be a non-negative integer or \"random\"."),
+    }
+}
 
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("sweep") => sweep::sweep(&args[2..]),
+        _ => train_network(&args),
+    }
+}
+
+fn train_network(args: &[String]) {
     let config_str = fs::read_to_string("config.ini").expect("Error while reading the configuration file.");
     let mut config = Ini::new();
     let _ = config.read(config_str);
 
-    let use_sample_data = config.getbool("general", "use_sample_data").unwrap().unwrap();
-    let epochs = config.getuint("general", "epochs").unwrap().unwrap() as u32;
-    let test_sample_count = config.getuint("general", "test_sample_count").unwrap().unwrap() as usize;
-    let hidden_layer_count = config.getint("hidden_layers", "layer_count").unwrap().unwrap() as i32;
-    let hidden_layer_width = config.getuint("hidden_layers", "layer_width").unwrap().unwrap() as u32;
-
-    let buffered = if use_sample_data {
-        BufReader::new(File::open("sample_data.csv").unwrap())
-    } else {
-        BufReader::new(File::open("data.csv").unwrap())
-    };
+    let use_sample_data = setting(&config, "general", "use_sample_data", true);
+    let data_file = setting(&config, "general", "data_file", "data.csv".to_string());
+    let test_file = config.get("general", "test_file").filter(|test_file| !test_file.is_empty());
+    let validation_file = config.get("general", "validation_file").filter(|validation_file| !validation_file.is_empty());
+    let validation_sample_count = setting(&config, "general", "validation_sample_count", 0usize);
+    let initial_nn_file = config.get("general", "initial_nn_file").filter(|initial_nn_file| !initial_nn_file.is_empty());
+    let color_encoding = setting(&config, "general", "color_encoding", "srgb".to_string());
+    let test_sample_count = setting(&config, "general", "test_sample_count", 1000usize);
+    let layers = layer_sizes(&config);
+    let training = Training::from_config(&config, &color_encoding);
+    let splitting = Splitting::from_config(&config);
+    let curve_format = setting(&config, "training", "curve", CurveFormat::None);
+
+    let summary_path = args.iter().position(|arg| arg == "--summary").map(|idx| {
+        args.get(idx + 1).expect("\"--summary\" must be followed by a file name.").clone()
+    });
+    let resume_seed = args.iter().position(|arg| arg == "--resume").map(|idx| {
+        args.get(idx + 1)
+            .and_then(|seed| seed.parse::<u64>().ok())
+            .expect("\"--resume\" must be followed by the seed of the run to continue.")
+    });
+
+    if let (Some(initial_nn_file), Some(_)) = (&initial_nn_file, resume_seed) {
+        println!("Ignoring the initial network \"{}\", the resumed run continues from its checkpoint.", initial_nn_file);
+    }
 
-    let mut data = Vec::new();
-    for line in buffered.lines() {
-        match line {
-            Ok(line) => {
-                if line.is_empty() {
-                    continue;
-                }
+    // Loaded before the samples so that a network of the wrong shape is rejected right away.
+    let initial_net = initial_nn_file.filter(|_| resume_seed.is_none()).map(|initial_nn_file| {
+        let net = Mlp::load(&initial_nn_file, &color_encoding);
+        if net.layer_sizes() != layers {
+            panic!(
+                "The network in \"{}\" has the layer sizes {:?} but the configuration has {:?}.",
+                initial_nn_file, net.layer_sizes(), layers,
+            );
+        }
+        println!("Continuing to train \"{}\"", initial_nn_file);
+        net
+    });
 
-                let nums = line.split(", ").map(|s| s.parse::<f64>().expect("Found an invalid sample.")).collect::<Vec<_>>();
-                if nums.len() != 10 {
-                    panic!("Found an invalid sample.");
-                }
+    let seed = resume_seed.unwrap_or_else(|| seed_setting(&config, args));
+    println!("Seed: {}", seed);
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
 
-                let (input, output) = nums.split_at(7);
+    let data_path = if use_sample_data {
+        "sample_data.csv"
+    } else {
+        &data_file
+    };
+    let data_settings = Data {
+        data_file: data_path.to_string(),
+        data_sha256: metadata::file_sha256(data_path),
+        color_encoding: color_encoding.clone(),
+        validation_file: validation_file.clone(),
+        validation_sample_count,
+        test_file: test_file.clone(),
+        test_sample_count,
+        splitting: splitting.clone(),
+    };
 
-                data.push((input.to_vec(), output.to_vec()));
-            },
-            Err(_) => {},
+    let resumed_state = resume_seed.map(|seed| {
+        let checkpoint = Checkpoint::load(seed);
+        if checkpoint.layers != layers {
+            panic!("The layer configuration differs from the one used by the run being resumed.");
         }
-    }
+        if checkpoint.data != data_settings {
+            panic!("The data configuration differs from the one used by the run being resumed.");
+        }
+        // The results don't depend on the thread count, so it may change.
+        if checkpoint.training != (Training { threads: checkpoint.training.threads, ..training.clone() }) {
+            panic!("The training configuration differs from the one used by the run being resumed.");
+        }
+        println!("Resuming after {} epochs", checkpoint.state.epoch);
+        checkpoint.state
+    });
+    let data = dataset::load(data_path, &color_encoding);
 
     println!("Valid sample count: {}", data.len());
 
-    let (training_data, test_data) = data.split_at(data.len() - test_sample_count);
-
-    let mut layers = vec![7];
-    for _ in 0..hidden_layer_count {
-        layers.push(hidden_layer_width);
+    metadata::validate(data_path, Some(&data_settings.data_sha256), data.len(), &color_encoding);
+
+    let separate_test_data = test_file.map(|test_file| {
+        let test_data = dataset::load(&test_file, &color_encoding);
+        println!("Test sample count: {}", test_data.len());
+        metadata::validate(&test_file, None, test_data.len(), &color_encoding);
+        test_data
+    });
+
+    let separate_validation_data = validation_file.map(|validation_file| {
+        let validation_data = dataset::load(&validation_file, &color_encoding);
+        println!("Validation sample count: {}", validation_data.len());
+        metadata::validate(&validation_file, None, validation_data.len(), &color_encoding);
+        validation_data
+    });
+
+    let validation_count = match separate_validation_data {
+        Some(_) => 0,
+        None => validation_sample_count,
+    };
+    let test_count = match separate_test_data {
+        Some(_) => 0,
+        None => test_sample_count,
+    };
+    check(validation_count + test_count < data.len(), "The validation and test sample counts must add up to less than the sample count.");
+
+    let split = split::split(&data, validation_count, test_count, &splitting, &color_encoding, &mut rng);
+    split.save(data_path, seed, &splitting);
+    let (training_data, validation_data, test_data) = split.apply(data);
+    let test_data = separate_test_data.unwrap_or(test_data);
+    let validation_data = separate_validation_data.or(Some(validation_data).filter(|validation_data| !validation_data.is_empty()));
+
+    let (input_size, output_size) = (layers[0] as usize, *layers.last().unwrap() as usize);
+    if input_size != dataset::INPUT_COUNT || output_size != dataset::OUTPUT_COUNT {
+        panic!(
+            "The samples have {} inputs and {} outputs but the network has {} and {}.",
+            dataset::INPUT_COUNT, dataset::OUTPUT_COUNT, input_size, output_size,
+        );
     }
-    layers.push(3);
 
-    let mut net = NN::new(&layers);
+    let mut state = resumed_state.unwrap_or_else(|| {
+        let net = initial_net.unwrap_or_else(|| Mlp::new(&layers, &color_encoding, &mut rng));
+        State::new(net, &training, rng)
+    });
+
+    let curve = Curve::open(seed, curve_format, resume_seed.map(|_| state.epoch));
 
     let instant = Instant::now();
 
-    net.train(&training_data)
-        .halt_condition( HaltCondition::Epochs(epochs) )
-        .log_interval( Some(1) )
-        //.momentum( 0.1 )
-        .rate( 0.3 )
-        .go();
+    train::train(&mut state, &training_data, validation_data.as_ref(), &training, &color_encoding, curve, |state| {
+        Checkpoint {
+            seed,
+            layers: layers.clone(),
+            data: data_settings.clone(),
+            training: training.clone(),
+            state: state.clone(),
+        }.save();
+    });
 
     println!("Elapsed: {:?}", instant.elapsed());
 
+    let net = &state.mlp;
     write!(File::create("nn.json").unwrap(), "{}", net.to_json()).unwrap();
 
-    let mut sum = 0.0;
-    for &(ref inputs, ref outputs) in test_data.iter() {
-        let results = net.run(inputs);
+    println!("Test sample error: {}", net.error(&test_data, &Loss::Mse));
+    Evaluation::new(net, &test_data, &color_encoding).print("Test");
+    if let Some((_, _, best)) = &state.best {
+        println!("Test sample error of the best network: {}", best.error(&test_data, &Loss::Mse));
+        Evaluation::new(best, &test_data, &color_encoding).print("Best network test");
+    }
 
-        let mut current_sum = 0.0;
-        for (&result, &target) in results.iter().zip(outputs.iter()) {
-            current_sum += (target - result).powi(2);
-        }
-        sum += current_sum / (results.len() as f64)
+    if let Some(summary_path) = summary_path {
+        let network = state.best.as_ref().map_or(&state.mlp, |(_, _, best)| best);
+        Summary {
+            epochs: state.epoch,
+            best_epoch: state.best.as_ref().map(|(epoch, _, _)| *epoch),
+            validation: validation_data.as_ref().map(|validation_data| Evaluation::new(network, validation_data, &color_encoding)),
+            test: Evaluation::new(network, &test_data, &color_encoding),
+        }.save(&summary_path);
     }
-    println!("Test sample error: {}", sum / test_data.len() as f64);
 }
@@ -0,0 +1,73 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// The part of the provenance metadata written by sample-generator that is needed to validate a dataset.
+#[derive(Deserialize)]
+struct Metadata {
+    tool: String,
+    version: String,
+    seed: u64,
+    row_count: u64,
+    columns: Vec<String>,
+    color_encoding: String,
+    iteration_count: i32,
+    min_width: i32,
+    #[serde(default)]
+    operations: Vec<String>,
+    sha256: String,
+}
+
+fn load(data_path: &str) -> Option<Metadata> {
+    let json = fs::read_to_string(format!("{}.meta.json", data_path)).ok()?;
+    Some(serde_json::from_str(&json).expect("Found invalid dataset metadata."))
+}
+
+/// The column names listed in the metadata, if there is any.
+pub fn columns(data_path: &str) -> Option<Vec<String>> {
+    load(data_path).map(|metadata| metadata.columns)
+}
+
+/// Checks the metadata stored in "[dataset file].meta.json", if there is any, against the loaded dataset. `sha256` is
+/// the hash of the file if it is already known, so that large datasets aren't read once more.
+pub fn validate(data_path: &str, sha256: Option<&str>, sample_count: usize, color_encoding: &str) {
+    let metadata = match load(data_path) {
+        Some(metadata) => metadata,
+        None => {
+            println!("No metadata found for \"{}\".", data_path);
+            return;
+        },
+    };
+
+    // sample-generator describes the content of datasets from other tools without their provenance.
+    if metadata.tool == "unknown" {
+        println!("Dataset of unknown provenance");
+    } else {
+        println!(
+            "Dataset generated by {} {} with seed {}, iteration_count {} and min_width {}",
+            metadata.tool, metadata.version, metadata.seed, metadata.iteration_count, metadata.min_width,
+        );
+    }
+    for operation in metadata.operations.iter() {
+        println!("\tthen: {}", operation);
+    }
+
+    if metadata.color_encoding != color_encoding && metadata.color_encoding != "all" {
+        panic!("The network is trained on \"{}\" colors but the dataset is encoded as \"{}\".", color_encoding, metadata.color_encoding);
+    }
+    if metadata.row_count != sample_count as u64 {
+        panic!("The dataset contains {} samples but its metadata lists {}.", sample_count, metadata.row_count);
+    }
+    if sha256.map_or_else(|| file_sha256(data_path), str::to_string) != metadata.sha256 {
+        panic!("The content of \"{}\" doesn't match the hash in its metadata.", data_path);
+    }
+}
+
+pub fn file_sha256(path: &str) -> String {
+    let mut hasher = Sha256::new();
+    let mut file = File::open(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+    io::copy(&mut file, &mut hasher).unwrap();
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
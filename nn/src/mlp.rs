@@ -0,0 +1,273 @@
+use std::fs;
+use std::thread;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::dataset::Samples;
+use crate::loss::Loss;
+
+/// Batches are split into chunks of this many samples, whose gradients are added in order whichever threads compute
+/// them.
+const CHUNK_LEN: usize = 8;
+
+/// Weights of every node of every layer, in the same shape as the network.
+pub type Weights = Vec<Vec<Vec<f64>>>;
+
+/// A multilayer perceptron with sigmoid activations, stored in the "nn.json" layout of the `nn` crate so that
+/// image-generator and existing models keep working: every node starts with its bias weight, followed by one weight per
+/// node of the previous layer.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Mlp {
+    layers: Weights,
+    num_inputs: u32,
+    /// Encoding of the colors the network was trained on, networks written before it was stored use sRGB.
+    #[serde(default = "srgb")]
+    color_encoding: String,
+}
+
+fn srgb() -> String {
+    "srgb".to_string()
+}
+
+impl Mlp {
+    /// The weights are drawn uniformly from [-0.5, 0.5], like the `nn` crate does.
+    pub fn new(layer_sizes: &[u32], color_encoding: &str, rng: &mut impl Rng) -> Mlp {
+        if layer_sizes.len() < 2 || layer_sizes.contains(&0) {
+            panic!("The network needs at least two layers, none of them empty.");
+        }
+
+        let layers = layer_sizes
+            .windows(2)
+            .map(|sizes| {
+                (0..sizes[1])
+                    .map(|_| (0..=sizes[0]).map(|_| rng.gen_range(-0.5..=0.5)).collect())
+                    .collect()
+            })
+            .collect();
+
+        Mlp { layers, num_inputs: layer_sizes[0], color_encoding: color_encoding.to_string() }
+    }
+
+    /// Loads a network in the "nn.json" layout, checking that every node has one weight per node of the previous layer
+    /// and that it was trained on colors in `color_encoding`.
+    pub fn load(path: &str, color_encoding: &str) -> Mlp {
+        let json = fs::read_to_string(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        let mlp: Mlp = serde_json::from_str(&json).unwrap_or_else(|_| panic!("The file \"{}\" isn't a valid network.", path));
+
+        let sizes = mlp.layer_sizes();
+        let valid = mlp.layers.iter().zip(sizes.iter()).all(|(layer, &previous)| {
+            !layer.is_empty() && layer.iter().all(|node| node.len() == previous as usize + 1)
+        });
+        if mlp.layers.is_empty() || !valid {
+            panic!("The file \"{}\" isn't a valid network.", path);
+        }
+        if mlp.color_encoding != color_encoding {
+            panic!("The network in \"{}\" was trained on \"{}\" colors but \"{}\" are configured.", path, mlp.color_encoding, color_encoding);
+        }
+        mlp
+    }
+
+    /// Sizes of the input layer, the hidden layers and the output layer.
+    pub fn layer_sizes(&self) -> Vec<u32> {
+        let mut sizes = vec![self.num_inputs];
+        sizes.extend(self.layers.iter().map(|layer| layer.len() as u32));
+        sizes
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn layers(&self) -> &Weights {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Weights {
+        &mut self.layers
+    }
+
+    pub fn run(&self, inputs: &[f64]) -> Vec<f64> {
+        if inputs.len() != self.num_inputs as usize {
+            panic!("The input has a different length than the network's input layer.");
+        }
+        self.forward(inputs).pop().unwrap()
+    }
+
+    /// The mean error of the outputs for the samples.
+    pub fn error(&self, data: &Samples, loss: &Loss) -> f64 {
+        data.iter().map(|(inputs, targets)| loss.error(&self.run(inputs), targets)).sum::<f64>() / data.len().max(1) as f64
+    }
+
+    /// The outputs of every layer, starting with the inputs.
+    fn forward(&self, inputs: &[f64]) -> Vec<Vec<f64>> {
+        let mut results = vec![inputs.to_vec()];
+        for layer in self.layers.iter() {
+            let previous = results.last().unwrap();
+            let layer_results = layer
+                .iter()
+                .map(|node| sigmoid(node[0] + node[1..].iter().zip(previous.iter()).map(|(w, v)| w * v).sum::<f64>()))
+                .collect();
+            results.push(layer_results);
+        }
+        results
+    }
+
+    pub fn zeros(&self) -> Weights {
+        self.layers.iter().map(|layer| layer.iter().map(|node| vec![0.0; node.len()]).collect()).collect()
+    }
+
+    /// Adds the gradient of the error of one sample to `gradient` and returns the error.
+    fn backpropagate(&self, inputs: &[f64], targets: &[f64], loss: &Loss, gradient: &mut Weights) -> f64 {
+        let results = self.forward(inputs);
+        let outputs = results.last().unwrap();
+        if targets.len() != outputs.len() {
+            panic!("The target has a different length than the network's output layer.");
+        }
+
+        let (error, derivatives) = loss.derivatives(outputs, targets);
+
+        // Derivatives of the error with respect to the weighted sums of the nodes of the current layer.
+        let mut deltas = outputs
+            .iter()
+            .zip(derivatives.iter())
+            .map(|(r, d)| d * r * (1.0 - r))
+            .collect::<Vec<_>>();
+
+        for layer_idx in (0..self.layers.len()).rev() {
+            let previous = &results[layer_idx];
+            for (node_gradient, delta) in gradient[layer_idx].iter_mut().zip(deltas.iter()) {
+                node_gradient[0] += delta;
+                for (g, v) in node_gradient[1..].iter_mut().zip(previous.iter()) {
+                    *g += delta * v;
+                }
+            }
+
+            if layer_idx > 0 {
+                deltas = previous
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, r)| {
+                        let sum = self.layers[layer_idx].iter().zip(deltas.iter()).map(|(node, delta)| node[idx + 1] * delta).sum::<f64>();
+                        sum * r * (1.0 - r)
+                    })
+                    .collect();
+            }
+        }
+
+        error
+    }
+
+    /// The gradient of the mean error of the samples at `indices` and the sum of their errors. The chunks of the batch
+    /// are spread over up to `threads` threads and their gradients added in order, so that the result doesn't depend on
+    /// the thread count.
+    pub fn gradient(&self, data: &Samples, indices: &[usize], loss: &Loss, threads: usize) -> (Weights, f64) {
+        let partial = |chunk: &[usize]| {
+            let mut gradient = self.zeros();
+            let error = chunk.iter().map(|&idx| self.backpropagate(data.inputs(idx), data.targets(idx), loss, &mut gradient)).sum::<f64>();
+            (gradient, error)
+        };
+
+        let chunks = indices.chunks(CHUNK_LEN).collect::<Vec<_>>();
+        let threads = threads.clamp(1, chunks.len().max(1));
+        let mut partials: Box<dyn Iterator<Item = (Weights, f64)>> = if threads == 1 {
+            Box::new(chunks.iter().map(|chunk| partial(chunk)))
+        } else {
+            let partials = thread::scope(|scope| {
+                let handles = chunks
+                    .chunks(chunks.len().div_ceil(threads))
+                    .map(|chunks| scope.spawn(move || chunks.iter().map(|chunk| partial(chunk)).collect::<Vec<_>>()))
+                    .collect::<Vec<_>>();
+                handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+            });
+            Box::new(partials.into_iter())
+        };
+
+        let (mut gradient, mut error) = partials.next().unwrap_or_else(|| (self.zeros(), 0.0));
+        for (partial_gradient, partial_error) in partials {
+            for (g, p) in gradient.iter_mut().flatten().flatten().zip(partial_gradient.iter().flatten().flatten()) {
+                *g += p;
+            }
+            error += partial_error;
+        }
+
+        let scale = 1.0 / indices.len().max(1) as f64;
+        for g in gradient.iter_mut().flatten().flatten() {
+            *g *= scale;
+        }
+        (gradient, error)
+    }
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    #[test]
+    fn networks_store_their_encoding() {
+        let path = std::env::temp_dir().join("nn_encoding.json").to_string_lossy().to_string();
+        let mlp = Mlp::new(&[7, 4, 3], "lab", &mut rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(1));
+        fs::write(&path, mlp.to_json()).unwrap();
+        let loaded = Mlp::load(&path, "lab");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.layers(), mlp.layers());
+    }
+
+    #[test]
+    fn gradients_dont_depend_on_the_thread_count() {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(2);
+        let mlp = Mlp::new(&[7, 16, 16, 3], "srgb", &mut rng);
+        let data = Samples::from_values((0..100 * 10).map(|_| rng.gen()).collect());
+        let indices = (0..100).rev().collect::<Vec<_>>();
+
+        for loss in [Loss::Mse, Loss::new("delta_e", "srgb")] {
+            let (gradient, error) = mlp.gradient(&data, &indices, &loss, 1);
+            for threads in [2, 3, 16] {
+                assert_eq!(mlp.gradient(&data, &indices, &loss, threads), (gradient.clone(), error));
+            }
+        }
+    }
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3);
+        let mlp = Mlp::new(&[7, 5, 3], "srgb", &mut rng);
+        let data = Samples::from_values((0..10 * 10).map(|_| rng.gen_range(0.1..0.9)).collect());
+        let indices = (0..10).collect::<Vec<_>>();
+        let step = 1e-5;
+
+        for loss in [Loss::Mse, Loss::new("delta_e", "srgb")] {
+            let (gradient, _) = mlp.gradient(&data, &indices, &loss, 1);
+            for (layer_idx, layer) in gradient.iter().enumerate() {
+                for (node_idx, node) in layer.iter().enumerate() {
+                    for (weight_idx, g) in node.iter().enumerate() {
+                        let mut shifted = mlp.clone();
+                        shifted.layers[layer_idx][node_idx][weight_idx] += step;
+                        let above = shifted.error(&data, &loss);
+                        shifted.layers[layer_idx][node_idx][weight_idx] -= 2.0 * step;
+                        let numeric = (above - shifted.error(&data, &loss)) / (2.0 * step);
+                        assert!((g - numeric).abs() <= 1e-4 * numeric.abs().max(1.0), "{} vs {}", g, numeric);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_sample_network_loads() {
+        let mlp = Mlp::load("../image-generator/sample_nn.json", "srgb");
+
+        assert_eq!(mlp.layer_sizes(), [7, 64, 64, 64, 64, 3]);
+        assert!(mlp.run(&[0.1, 0.2, 0.3, 0.9, 0.8, 0.7, 0.5]).iter().all(|v| (0.0..=1.0).contains(v)));
+    }
+
+    #[test]
+    #[should_panic(expected = "was trained on \"srgb\" colors but \"linear_rgb\" are configured")]
+    fn networks_of_other_encodings_are_rejected() {
+        Mlp::load("../image-generator/sample_nn.json", "linear_rgb");
+    }
+}
@@ -0,0 +1,114 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::mlp::{Mlp, Weights};
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizerKind {
+    Sgd,
+    Adam,
+    /// Adam with weight decay that is decoupled from the gradient.
+    AdamW,
+}
+
+impl FromStr for OptimizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sgd" => Ok(OptimizerKind::Sgd),
+            "adam" => Ok(OptimizerKind::Adam),
+            "adamw" => Ok(OptimizerKind::AdamW),
+            _ => Err(format!("Unknown optimizer \"{}\".", s)),
+        }
+    }
+}
+
+/// How the learning rate changes over the epochs.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    Constant,
+    /// Multiplied by `factor` every `epochs` epochs.
+    Step { epochs: u32, factor: f64 },
+    /// Multiplied by `factor` every epoch.
+    Exponential { factor: f64 },
+    /// Follows half a cosine from the initial rate down to `min_rate` over the whole training.
+    Cosine { min_rate: f64 },
+}
+
+impl Schedule {
+    pub fn rate(&self, initial_rate: f64, epoch: u32, epochs: u32) -> f64 {
+        match *self {
+            Schedule::Constant => initial_rate,
+            Schedule::Step { epochs: step_epochs, factor } => initial_rate * factor.powi((epoch / step_epochs.max(1)) as i32),
+            Schedule::Exponential { factor } => initial_rate * factor.powi(epoch as i32),
+            Schedule::Cosine { min_rate } => {
+                let progress = epoch as f64 / epochs.max(1) as f64;
+                min_rate + (initial_rate - min_rate) * 0.5 * (1.0 + (PI * progress).cos())
+            },
+        }
+    }
+}
+
+/// Applies gradients to the weights, keeping the per-weight state of the optimizer between steps.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Optimizer {
+    kind: OptimizerKind,
+    momentum: f64,
+    weight_decay: f64,
+    step: u64,
+    /// Velocity for SGD, first moment estimate for Adam.
+    first: Vec<f64>,
+    second: Vec<f64>,
+}
+
+impl Optimizer {
+    pub fn new(kind: OptimizerKind, momentum: f64, weight_decay: f64, mlp: &Mlp) -> Optimizer {
+        let weight_count = mlp.layers().iter().flatten().flatten().count();
+        Optimizer {
+            kind,
+            momentum,
+            weight_decay,
+            step: 0,
+            first: vec![0.0; weight_count],
+            second: vec![0.0; weight_count],
+        }
+    }
+
+    pub fn step(&mut self, mlp: &mut Mlp, gradient: &Weights, rate: f64) {
+        self.step += 1;
+        let bias_correction1 = 1.0 - BETA1.powi(self.step as i32);
+        let bias_correction2 = 1.0 - BETA2.powi(self.step as i32);
+
+        let mut idx = 0;
+        for (node, node_gradient) in mlp.layers_mut().iter_mut().flatten().zip(gradient.iter().flatten()) {
+            for (weight_idx, (w, g)) in node.iter_mut().zip(node_gradient.iter()).enumerate() {
+                match self.kind {
+                    OptimizerKind::Sgd => {
+                        self.first[idx] = self.momentum * self.first[idx] - rate * g;
+                        *w += self.first[idx];
+                    },
+                    OptimizerKind::Adam | OptimizerKind::AdamW => {
+                        self.first[idx] = BETA1 * self.first[idx] + (1.0 - BETA1) * g;
+                        self.second[idx] = BETA2 * self.second[idx] + (1.0 - BETA2) * g * g;
+                        let first = self.first[idx] / bias_correction1;
+                        let second = self.second[idx] / bias_correction2;
+
+                        // The bias, the first weight of every node, isn't decayed.
+                        if self.kind == OptimizerKind::AdamW && weight_idx > 0 {
+                            *w -= rate * self.weight_decay * *w;
+                        }
+                        *w -= rate * first / (second.sqrt() + EPSILON);
+                    },
+                }
+                idx += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::str::FromStr;
+use configparser::ini::Ini;
+
+/// A value of config.ini, or `default` if it is missing or empty.
+pub fn setting<T: FromStr>(config: &Ini, section: &str, key: &str, default: T) -> T {
+    match config.get(section, key).filter(|value| !value.is_empty()) {
+        Some(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("Found an invalid value \"{}\" for \"{}\" in the \"{}\" section.", value, key, section)),
+        None => default,
+    }
+}
+
+/// Panics with `message` if `valid` is false.
+pub fn check(valid: bool, message: &str) {
+    if !valid {
+        panic!("{}", message);
+    }
+}
+
+/// Sizes of the input layer, the hidden layers and the output layer.
+pub fn layer_sizes(config: &Ini) -> Vec<u32> {
+    let input_size = setting(config, "layers", "input_size", 7u32);
+    let output_size = setting(config, "layers", "output_size", 3u32);
+    let hidden_layer_count = setting(config, "hidden_layers", "layer_count", 3u32);
+    let hidden_layer_width = setting(config, "hidden_layers", "layer_width", 16u32);
+    check(input_size > 0 && output_size > 0, "The input and output layers must not be empty.");
+    check(hidden_layer_count == 0 || hidden_layer_width > 0, "The hidden layers must not be empty.");
+
+    let mut layers = vec![input_size];
+    for _ in 0..hidden_layer_count {
+        layers.push(hidden_layer_width);
+    }
+    layers.push(output_size);
+    layers
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use std::ops::Range;
+use std::str::FromStr;
+use configparser::ini::Ini;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use crate::color::cielab_dist_ciede2000;
+use crate::dataset::{self, Samples};
+use crate::settings::{check, setting};
+
+/// Where the held-out samples are recorded.
+pub const SPLIT_PATH: &str = "split.json";
+
+/// A step between neighbouring samples of a gradient is a discontinuity if its CIEDE2000 distance is this many times
+/// larger than the one expected from the endpoint distance, like in the coverage report of sample-generator.
+const JUMP_FACTOR: f64 = 3.0;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stratify {
+    None,
+    /// By quantiles of the CIEDE2000 distance between the endpoints.
+    DeltaE,
+    /// By whether a gradient has a discontinuity, sequence datasets only.
+    Discontinuity,
+}
+
+impl FromStr for Stratify {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Stratify::None),
+            "delta_e" => Ok(Stratify::DeltaE),
+            "discontinuity" => Ok(Stratify::Discontinuity),
+            _ => Err(format!("Unknown stratification \"{}\".", s)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Splitting {
+    pub shuffle: bool,
+    pub stratify: Stratify,
+    /// Number of quantiles when stratifying by the endpoint distance.
+    pub strata: usize,
+}
+
+impl Splitting {
+    pub fn from_config(config: &Ini) -> Splitting {
+        let strata = setting(config, "split", "strata", 10usize);
+        check(strata > 0, "There must be at least one stratum.");
+
+        Splitting {
+            shuffle: setting(config, "split", "shuffle", true),
+            stratify: setting(config, "split", "stratify", Stratify::None),
+            strata,
+        }
+    }
+}
+
+/// Indices of the samples that are held out for validation and testing, the rest are used for training.
+#[derive(Serialize)]
+pub struct Split {
+    pub validation: Vec<usize>,
+    pub test: Vec<usize>,
+}
+
+/// What is written to `SPLIT_PATH`.
+#[derive(Serialize)]
+struct Record<'a> {
+    data_file: &'a str,
+    seed: u64,
+    shuffle: bool,
+    stratify: Stratify,
+    strata: usize,
+    #[serde(flatten)]
+    split: &'a Split,
+}
+
+/// Holds out about `validation_count` and `test_count` samples, the same share of every stratum. The samples of a
+/// gradient, consecutive samples with the same or swapped endpoints, are kept together so that its neighbouring samples don't end
+/// up on both sides. Without shuffling, the last samples of every stratum are held out.
+pub fn split(
+    data: &Samples,
+    validation_count: usize,
+    test_count: usize,
+    splitting: &Splitting,
+    color_encoding: &str,
+    rng: &mut impl Rng,
+) -> Split {
+    let groups = groups(data);
+
+    let mut strata = match splitting.stratify {
+        Stratify::None => vec![groups],
+        Stratify::DeltaE => {
+            let distance = |group: &Range<usize>| {
+                let inputs = data.inputs(group.start);
+                cielab_dist_ciede2000(&dataset::cielab(&inputs[0..3], color_encoding), &dataset::cielab(&inputs[3..6], color_encoding))
+            };
+            let mut sorted = groups.iter().map(|group| (distance(group), group.clone())).collect::<Vec<_>>();
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let strata_count = splitting.strata.min(sorted.len()).max(1);
+            let mut strata = (0..strata_count)
+                .map(|k| {
+                    let quantile = &sorted[k * sorted.len() / strata_count..(k + 1) * sorted.len() / strata_count];
+                    quantile.iter().map(|(_, group)| group.clone()).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            for stratum in strata.iter_mut() {
+                stratum.sort_by_key(|group| group.start);
+            }
+            strata
+        },
+        Stratify::Discontinuity => {
+            check(groups.iter().any(|group| group.len() > 1), "Stratifying by discontinuity needs a sequence dataset.");
+            let (discontinuous, continuous) = groups.into_iter().partition(|group| has_discontinuity(data, group, color_encoding));
+            vec![discontinuous, continuous]
+        },
+    };
+
+    let mut split = Split { validation: Vec::new(), test: Vec::new() };
+    for stratum in strata.iter_mut() {
+        if splitting.shuffle {
+            stratum.shuffle(rng);
+        }
+
+        let sample_count = stratum.iter().map(|group| group.len()).sum::<usize>();
+        let share = |count: usize| (sample_count * count + data.len() / 2) / data.len().max(1);
+        for (indices, count) in [(&mut split.test, share(test_count)), (&mut split.validation, share(validation_count))] {
+            let mut taken = 0;
+            while taken < count {
+                let Some(group) = stratum.pop() else { break };
+                taken += group.len();
+                indices.extend(group);
+            }
+        }
+    }
+
+    split.validation.sort_unstable();
+    split.test.sort_unstable();
+    split
+}
+
+impl Split {
+    pub fn save(&self, data_file: &str, seed: u64, splitting: &Splitting) {
+        let record = Record {
+            data_file,
+            seed,
+            shuffle: splitting.shuffle,
+            stratify: splitting.stratify,
+            strata: splitting.strata,
+            split: self,
+        };
+        fs::write(SPLIT_PATH, serde_json::to_string(&record).unwrap()).unwrap();
+    }
+
+    /// Moves the samples into the training, validation and test sets, keeping their order.
+    pub fn apply(&self, data: Samples) -> (Samples, Samples, Samples) {
+        let mut set = vec![0u8; data.len()];
+        for &idx in self.validation.iter() {
+            set[idx] = 1;
+        }
+        for &idx in self.test.iter() {
+            set[idx] = 2;
+        }
+
+        let mut training = Samples::with_capacity(data.len() - self.validation.len() - self.test.len());
+        let mut validation = Samples::with_capacity(self.validation.len());
+        let mut test = Samples::with_capacity(self.test.len());
+        for (idx, set) in set.into_iter().enumerate() {
+            match set {
+                0 => training.push_from(&data, idx),
+                1 => validation.push_from(&data, idx),
+                _ => test.push_from(&data, idx),
+            }
+        }
+        (training, validation, test)
+    }
+}
+
+/// The endpoints of a sample in a fixed order, equal for a sample and its mirror.
+fn endpoints(inputs: &[f64]) -> [&[f64]; 2] {
+    let (c1, c2) = (&inputs[0..3], &inputs[3..6]);
+    if c1.iter().map(|v| v.to_bits()).le(c2.iter().map(|v| v.to_bits())) {
+        [c1, c2]
+    } else {
+        [c2, c1]
+    }
+}
+
+/// Ranges of consecutive samples with the same or swapped endpoints, so that mirrored samples stay with their
+/// originals.
+fn groups(data: &Samples) -> Vec<Range<usize>> {
+    let mut groups: Vec<Range<usize>> = Vec::new();
+    for (idx, (inputs, _)) in data.iter().enumerate() {
+        match groups.last_mut() {
+            Some(group) if endpoints(data.inputs(group.start)) == endpoints(inputs) => group.end = idx + 1,
+            _ => groups.push(idx..idx + 1),
+        }
+    }
+    groups
+}
+
+fn has_discontinuity(data: &Samples, group: &Range<usize>, color_encoding: &str) -> bool {
+    let inputs = data.inputs(group.start);
+    let distance = cielab_dist_ciede2000(&dataset::cielab(&inputs[0..3], color_encoding), &dataset::cielab(&inputs[3..6], color_encoding));
+
+    // Only neighbours of the same direction are steps of a gradient, not the last original and the first mirrored sample.
+    (group.start..group.end.saturating_sub(1)).filter(|&idx| data.inputs(idx)[0..6] == data.inputs(idx + 1)[0..6]).any(|idx| {
+        let (a, b) = (idx, idx + 1);
+        let step = cielab_dist_ciede2000(&dataset::cielab(data.targets(a), color_encoding), &dataset::cielab(data.targets(b), color_encoding));
+        let expected = distance * (data.inputs(b)[6] - data.inputs(a)[6]).abs();
+        step > JUMP_FACTOR * expected.max(1.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    /// Gradients of 4 samples each followed by their mirrors, with endpoints whose channels are all `k / 20`.
+    fn mirrored_gradients(count: usize) -> Samples {
+        let mut values = Vec::new();
+        for k in 0..count {
+            let (c1, c2) = ([k as f64 / 20.0; 3], [1.0 - k as f64 / 20.0; 3]);
+            for (from, to, mirrored) in [(c1, c2, false), (c2, c1, true)] {
+                for step in 1..=4 {
+                    let t = step as f64 / 5.0;
+                    let t = if mirrored { 1.0 - t } else { t };
+                    let out = c1.map(|v| v + (c2[0] - c1[0]) * t);
+                    values.extend(from.iter().chain(to.iter()).chain([t].iter()).chain(out.iter()));
+                }
+            }
+        }
+        Samples::from_values(values)
+    }
+
+    #[test]
+    fn mirrors_stay_with_their_originals() {
+        let data = mirrored_gradients(10);
+        assert_eq!(groups(&data), (0..10).map(|k| k * 8..(k + 1) * 8).collect::<Vec<_>>());
+        assert!(!has_discontinuity(&data, &(0..8), "srgb"));
+
+        for stratify in [Stratify::None, Stratify::DeltaE, Stratify::Discontinuity] {
+            let splitting = Splitting { shuffle: true, stratify, strata: 2 };
+            let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3);
+            let split = split(&data, 16, 16, &splitting, "srgb", &mut rng);
+
+            for held_out in [&split.validation, &split.test] {
+                assert_eq!(held_out.len(), 16);
+                for &idx in held_out.iter() {
+                    let original = idx - idx % 8;
+                    assert!((original..original + 8).all(|idx| held_out.contains(&idx)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::env;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use configparser::ini::Ini;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+use crate::evaluation::Evaluation;
+use crate::seed_setting;
+use crate::settings::{check, setting};
+
+/// Settings whose values are paths, made absolute because every run trains in its own directory.
+const PATH_SETTINGS: [(&str, &str); 4] = [
+    ("general", "data_file"),
+    ("general", "test_file"),
+    ("general", "validation_file"),
+    ("general", "initial_nn_file"),
+];
+
+/// What a run writes to the file given with "--summary", for the leaderboard.
+#[derive(Serialize, Deserialize)]
+pub struct Summary {
+    pub epochs: u32,
+    pub best_epoch: Option<u32>,
+    /// Of the best network if there is one, otherwise of the final network.
+    pub validation: Option<Evaluation>,
+    pub test: Evaluation,
+}
+
+impl Summary {
+    pub fn save(&self, path: &str) {
+        fs::write(path, serde_json::to_string(self).unwrap()).unwrap();
+    }
+}
+
+/// A setting and the values to try.
+struct Parameter {
+    section: String,
+    key: String,
+    values: Values,
+}
+
+enum Values {
+    List(Vec<String>),
+    /// Sampled uniformly by random sweeps, as integers if both bounds are integers.
+    Range(f64, f64, bool),
+    /// Sampled uniformly on a log scale by random sweeps, for settings spanning orders of magnitude.
+    LogRange(f64, f64),
+}
+
+impl Parameter {
+    fn parse(name: &str, value: &str) -> Parameter {
+        let (section, key) = name
+            .split_once('.')
+            .unwrap_or_else(|| panic!("The sweep parameter \"{}\" must be given as section.key.", name));
+
+        let (log, range) = match value.trim().strip_prefix("log ") {
+            Some(range) => (true, range),
+            None => (false, value),
+        };
+        // Anything else containing "..", like a relative path, is a list.
+        let bounds = range
+            .split_once("..")
+            .and_then(|(min, max)| Some((min.trim(), max.trim(), min.trim().parse::<f64>().ok()?, max.trim().parse::<f64>().ok()?)));
+
+        let values = match bounds {
+            Some((min, max, min_value, max_value)) => {
+                check(min_value <= max_value, &format!("The range of \"{}\" must not be empty.", name));
+                if log {
+                    check(min_value > 0.0, &format!("The log range of \"{}\" must be positive.", name));
+                    Values::LogRange(min_value, max_value)
+                } else {
+                    Values::Range(min_value, max_value, min.parse::<i64>().is_ok() && max.parse::<i64>().is_ok())
+                }
+            },
+            None if log => panic!("Found an invalid log range for \"{}\".", name),
+            None => Values::List(value.split(',').map(|v| v.trim().to_string()).collect()),
+        };
+
+        Parameter { section: section.to_string(), key: key.to_string(), values }
+    }
+
+    fn random_value(&self, rng: &mut impl Rng) -> String {
+        match &self.values {
+            Values::List(values) => values[rng.gen_range(0..values.len())].clone(),
+            Values::Range(min, max, true) => rng.gen_range(*min as i64..=*max as i64).to_string(),
+            Values::Range(min, max, false) => rng.gen_range(*min..=*max).to_string(),
+            Values::LogRange(min, max) => rng.gen_range(min.ln()..=max.ln()).exp().to_string(),
+        }
+    }
+}
+
+/// Every combination of the values of the parameters, the last parameter changing fastest.
+fn grid(parameters: &[Parameter]) -> Vec<Vec<String>> {
+    let mut runs = vec![Vec::new()];
+    for parameter in parameters {
+        let Values::List(values) = &parameter.values else {
+            panic!("Ranges are only supported by random sweeps, \"{}.{}\" has one.", parameter.section, parameter.key);
+        };
+        runs = runs
+            .iter()
+            .flat_map(|run| values.iter().map(move |value| {
+                let mut run = run.clone();
+                run.push(value.clone());
+                run
+            }))
+            .collect();
+    }
+    runs
+}
+
+/// `nn sweep [specification]` trains a network for every combination of the settings in the `[VALUES]` section of the
+/// specification ("sweep.ini" by default) on top of config.ini, each in its own directory "sweep_[seed]/run_[n]", and
+/// ranks them by the mean CIEDE2000 error of their validation samples in "sweep_[seed]/leaderboard.csv".
+pub fn sweep(args: &[String]) {
+    let spec_path = args.first().filter(|arg| !arg.starts_with("--")).map_or("sweep.ini", String::as_str);
+
+    let config_str = fs::read_to_string("config.ini").expect("Error while reading the configuration file.");
+    let mut config = Ini::new();
+    let _ = config.read(config_str);
+    let spec_str = fs::read_to_string(spec_path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", spec_path));
+    let mut spec = Ini::new();
+    let _ = spec.read(spec_str);
+
+    let mut parameters = spec
+        .get_map_ref()
+        .get("values")
+        .map(|values| values.iter().map(|(name, value)| Parameter::parse(name, value.as_deref().unwrap_or(""))).collect::<Vec<_>>())
+        .unwrap_or_default();
+    // Sorted, the specification doesn't keep the order of its settings.
+    parameters.sort_by(|a, b| (&a.section, &a.key).cmp(&(&b.section, &b.key)));
+    check(!parameters.is_empty(), "The sweep specification has no parameters in its VALUES section.");
+
+    let seed = seed_setting(&config, args);
+    println!("Seed: {}", seed);
+
+    let runs = match setting(&spec, "sweep", "mode", "grid".to_string()).as_str() {
+        "grid" => grid(&parameters),
+        "random" => {
+            let run_count = setting(&spec, "sweep", "run_count", 8usize);
+            check(run_count > 0, "A random sweep needs at least one run.");
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+            (0..run_count).map(|_| parameters.iter().map(|parameter| parameter.random_value(&mut rng)).collect()).collect()
+        },
+        mode => panic!("Unknown sweep mode \"{}\".", mode),
+    };
+
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+    let parallel_runs = match setting(&spec, "sweep", "parallel_runs", 1usize) {
+        0 => cores,
+        parallel_runs => parallel_runs,
+    }.min(runs.len());
+
+    // The runs share the seed, so they hold out the same samples, and split the cores unless the threads are swept.
+    let mut base = config.clone();
+    base.set("training", "threads", Some((cores / parallel_runs).max(1).to_string()));
+
+    let sweep_dir = format!("sweep_{}", seed);
+    fs::create_dir_all(&sweep_dir).unwrap();
+    for (run_idx, values) in runs.iter().enumerate() {
+        let mut run_config = base.clone();
+        for (parameter, value) in parameters.iter().zip(values.iter()) {
+            run_config.set(&parameter.section, &parameter.key, Some(value.clone()));
+        }
+        check(
+            run_config.get("general", "validation_file").is_some_and(|path| !path.is_empty())
+                || setting(&run_config, "general", "validation_sample_count", 0usize) > 0,
+            "A sweep needs validation samples, set validation_file or validation_sample_count.",
+        );
+
+        if setting(&run_config, "general", "use_sample_data", true) {
+            run_config.set("general", "use_sample_data", Some("false".to_string()));
+            run_config.set("general", "data_file", Some("sample_data.csv".to_string()));
+        }
+        for (section, key) in PATH_SETTINGS {
+            if let Some(path) = run_config.get(section, key).filter(|path| !path.is_empty()) {
+                let path = fs::canonicalize(&path).unwrap_or_else(|_| panic!("Couldn't find the file \"{}\"", path));
+                run_config.set(section, key, Some(path.to_string_lossy().to_string()));
+            }
+        }
+
+        let run_dir = Path::new(&sweep_dir).join(format!("run_{}", run_idx));
+        fs::create_dir_all(&run_dir).unwrap();
+        run_config.write(run_dir.join("config.ini")).unwrap();
+    }
+
+    println!("Training {} networks, {} at a time...", runs.len(), parallel_runs);
+    let exe = env::current_exe().unwrap();
+    let next_run = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..parallel_runs {
+            scope.spawn(|| loop {
+                let run_idx = next_run.fetch_add(1, Ordering::Relaxed);
+                if run_idx >= runs.len() {
+                    break;
+                }
+                let run_dir = Path::new(&sweep_dir).join(format!("run_{}", run_idx));
+                let output = File::create(run_dir.join("output.txt")).unwrap();
+                let status = Command::new(&exe)
+                    .args(["--seed", &seed.to_string(), "--summary", "summary.json"])
+                    .current_dir(&run_dir)
+                    .stdout(output.try_clone().unwrap())
+                    .stderr(Stdio::from(output))
+                    .status()
+                    .unwrap();
+                println!("\tRun {} of {} {}", run_idx + 1, runs.len(), if status.success() { "finished" } else { "failed" });
+            });
+        }
+    });
+
+    let summaries = (0..runs.len())
+        .map(|run_idx| {
+            let path = Path::new(&sweep_dir).join(format!("run_{}", run_idx)).join("summary.json");
+            fs::read_to_string(path).ok().and_then(|json| serde_json::from_str::<Summary>(&json).ok())
+        })
+        .collect::<Vec<_>>();
+    let validation_mean = |run_idx: usize| summaries[run_idx].as_ref().and_then(|summary| summary.validation.as_ref()).map(|v| v.mean);
+
+    // Failed runs go last.
+    let mut ranking = (0..runs.len()).collect::<Vec<_>>();
+    ranking.sort_by(|&a, &b| validation_mean(a).unwrap_or(f64::INFINITY).total_cmp(&validation_mean(b).unwrap_or(f64::INFINITY)));
+
+    let mut csv = ["rank", "run", "epochs", "best_epoch", "validation_mean", "validation_median", "validation_p95", "validation_p99",
+        "validation_max", "validation_under_1", "validation_under_2", "test_mean"]
+        .iter()
+        .map(|column| column.to_string())
+        .chain(parameters.iter().map(|parameter| format!("{}.{}", parameter.section, parameter.key)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    csv.push('\n');
+    for (rank, &run_idx) in ranking.iter().enumerate() {
+        let mut row = vec![(rank + 1).to_string(), format!("run_{}", run_idx)];
+        match &summaries[run_idx] {
+            Some(summary) => {
+                row.push(summary.epochs.to_string());
+                row.push(summary.best_epoch.map_or(String::new(), |epoch| epoch.to_string()));
+                match &summary.validation {
+                    Some(v) => row.extend([v.mean, v.median, v.p95, v.p99, v.max, v.under_1, v.under_2].map(|v| v.to_string())),
+                    None => row.extend([""; 7].map(String::from)),
+                }
+                row.push(summary.test.mean.to_string());
+            },
+            None => row.extend([""; 10].map(String::from)),
+        }
+        row.extend(runs[run_idx].iter().cloned());
+        csv += &row.join(", ");
+        csv.push('\n');
+    }
+    fs::write(Path::new(&sweep_dir).join("leaderboard.csv"), csv).unwrap();
+
+    println!("Leaderboard, by the mean CIEDE2000 error of the validation samples:");
+    for (rank, &run_idx) in ranking.iter().enumerate().take(10) {
+        let settings = parameters
+            .iter()
+            .zip(runs[run_idx].iter())
+            .map(|(parameter, value)| format!("{}.{} = {}", parameter.section, parameter.key, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match validation_mean(run_idx) {
+            Some(mean) => println!("\t{}. run_{}: {:.3} ({})", rank + 1, run_idx, mean, settings),
+            None => println!("\t{}. run_{}: failed, see its output.txt ({})", rank + 1, run_idx, settings),
+        }
+    }
+    println!("The full leaderboard is in \"{}\".", Path::new(&sweep_dir).join("leaderboard.csv").display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_parse_lists_and_ranges() {
+        let values = |value: &str| Parameter::parse("training.x", value).values;
+
+        assert!(matches!(values("0.001, 0.003"), Values::List(values) if values == ["0.001", "0.003"]));
+        assert!(matches!(values("2..5"), Values::Range(2.0, 5.0, true)));
+        assert!(matches!(values(" 0.5 .. 1"), Values::Range(0.5, 1.0, false)));
+        assert!(matches!(values("log 1e-4..1e-1"), Values::LogRange(1e-4, 1e-1)));
+        assert!(matches!(values("../data.bin, ../more..data.bin"), Values::List(values) if values == ["../data.bin", "../more..data.bin"]));
+    }
+
+    #[test]
+    #[should_panic(expected = "The log range of \"training.x\" must be positive.")]
+    fn log_ranges_must_be_positive() {
+        Parameter::parse("training.x", "log 0..1");
+    }
+
+    #[test]
+    fn log_ranges_are_sampled_on_a_log_scale() {
+        let parameter = Parameter::parse("training.x", "log 1e-4..1");
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let values = (0..1000).map(|_| parameter.random_value(&mut rng).parse::<f64>().unwrap()).collect::<Vec<_>>();
+
+        assert!(values.iter().all(|v| (1e-4..=1.0).contains(v)));
+        // A quarter of the log scale is below 1e-3, against a thousandth of the linear one.
+        let below = values.iter().filter(|&&v| v < 1e-3).count();
+        assert!((200..300).contains(&below), "{}", below);
+    }
+
+    #[test]
+    fn grids_combine_every_value() {
+        let parameters = [Parameter::parse("a.x", "1, 2"), Parameter::parse("b.y", "p, q, r")];
+
+        assert_eq!(grid(&parameters), [["1", "p"], ["1", "q"], ["1", "r"], ["2", "p"], ["2", "q"], ["2", "r"]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Ranges are only supported by random sweeps")]
+    fn grids_reject_ranges() {
+        grid(&[Parameter::parse("a.x", "1..2")]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use configparser::ini::Ini;
+use rand::seq::SliceRandom;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+use crate::curve::{Curve, Point};
+use crate::dataset::Samples;
+use crate::evaluation::Evaluation;
+use crate::loss::Loss;
+use crate::mlp::Mlp;
+use crate::optimizer::{Optimizer, OptimizerKind, Schedule};
+use crate::settings::{check, setting};
+
+/// Where the network with the lowest validation error is saved during training.
+pub const BEST_PATH: &str = "nn_best.json";
+
+/// When training stops, in addition to `epochs`, which always bounds it.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaltCondition {
+    Epochs,
+    /// Once the mean training loss of an epoch is at most this.
+    Loss(f64),
+    Time(Duration),
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Training {
+    pub epochs: u32,
+    pub loss: Loss,
+    pub halt_condition: HaltCondition,
+    pub batch_size: usize,
+    pub learning_rate: f64,
+    pub schedule: Schedule,
+    pub optimizer: OptimizerKind,
+    pub momentum: f64,
+    pub weight_decay: f64,
+    pub threads: usize,
+    /// Epochs between progress reports, 0 disables them.
+    pub log_interval: u32,
+    /// Epochs between evaluations of the validation samples.
+    pub validation_interval: u32,
+    /// Evaluations without an improvement of the validation error before training stops, 0 disables early stopping.
+    pub patience: u32,
+    /// Epochs between checkpoints, 0 disables them.
+    pub checkpoint_interval: u32,
+}
+
+impl Training {
+    pub fn from_config(config: &Ini, color_encoding: &str) -> Training {
+        let epochs = setting(config, "general", "epochs", 100u32);
+        check(epochs > 0, "Training needs at least one epoch.");
+
+        let halt_condition = match setting(config, "training", "halt_condition", "epochs".to_string()).as_str() {
+            "epochs" => HaltCondition::Epochs,
+            "loss" => {
+                let target_loss = setting(config, "training", "target_loss", 0.001);
+                check(target_loss > 0.0, "The target loss must be positive.");
+                HaltCondition::Loss(target_loss)
+            },
+            "time" => {
+                let time_limit: f64 = setting(config, "training", "time_limit", 3600.0);
+                check(time_limit > 0.0 && time_limit.is_finite(), "The time limit must be a positive number of seconds.");
+                HaltCondition::Time(Duration::from_secs_f64(time_limit))
+            },
+            halt_condition => panic!("Unknown halt condition \"{}\".", halt_condition),
+        };
+
+        let learning_rate: f64 = setting(config, "training", "learning_rate", 0.001);
+        check(learning_rate > 0.0 && learning_rate.is_finite(), "The learning rate must be positive.");
+
+        let decay_factor = setting(config, "training", "decay_factor", 0.5);
+        let schedule = match setting(config, "training", "schedule", "constant".to_string()).as_str() {
+            "constant" => Schedule::Constant,
+            "step" => {
+                let decay_epochs = setting(config, "training", "decay_epochs", 25u32);
+                check(decay_epochs > 0, "The learning rate must be decayed every one or more epochs.");
+                Schedule::Step { epochs: decay_epochs, factor: decay_factor }
+            },
+            "exponential" => Schedule::Exponential { factor: decay_factor },
+            "cosine" => {
+                let min_rate = setting(config, "training", "min_learning_rate", 0.00001);
+                check((0.0..=learning_rate).contains(&min_rate), "The minimum learning rate must be between 0 and the learning rate.");
+                Schedule::Cosine { min_rate }
+            },
+            schedule => panic!("Unknown learning rate schedule \"{}\".", schedule),
+        };
+        check(decay_factor > 0.0 && decay_factor <= 1.0, "The decay factor must be greater than 0 and at most 1.");
+
+        let batch_size = setting(config, "training", "batch_size", 32usize);
+        check(batch_size > 0, "The batch size must be positive.");
+
+        let momentum = setting(config, "training", "momentum", 0.0);
+        check((0.0..1.0).contains(&momentum), "The momentum must be at least 0 and less than 1.");
+
+        let weight_decay: f64 = setting(config, "training", "weight_decay", 0.0001);
+        check(weight_decay >= 0.0 && weight_decay.is_finite(), "The weight decay must not be negative.");
+
+        let validation_interval = setting(config, "training", "validation_interval", 1u32);
+        check(validation_interval > 0, "The validation samples must be evaluated every one or more epochs.");
+
+        let threads = match setting(config, "training", "threads", 0usize) {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        };
+
+        Training {
+            epochs,
+            loss: Loss::new(&setting(config, "training", "loss", "mse".to_string()), color_encoding),
+            halt_condition,
+            batch_size,
+            learning_rate,
+            schedule,
+            optimizer: setting(config, "training", "optimizer", OptimizerKind::Adam),
+            momentum,
+            weight_decay,
+            threads,
+            log_interval: setting(config, "training", "log_interval", 1u32),
+            validation_interval,
+            patience: setting(config, "training", "patience", 10u32),
+            checkpoint_interval: setting(config, "training", "checkpoint_interval", 1u32),
+        }
+    }
+}
+
+/// Everything that changes from epoch to epoch, so that training can be continued exactly from a checkpoint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct State {
+    /// Epochs trained so far.
+    pub epoch: u32,
+    pub mlp: Mlp,
+    pub optimizer: Optimizer,
+    pub rng: Xoshiro256PlusPlus,
+    /// The epoch, validation error and network of the lowest validation error so far.
+    pub best: Option<(u32, f64, Mlp)>,
+    pub evaluations_since_best: u32,
+    /// Training time so far, for the time limit.
+    pub elapsed: Duration,
+}
+
+impl State {
+    pub fn new(mlp: Mlp, training: &Training, rng: Xoshiro256PlusPlus) -> State {
+        State {
+            epoch: 0,
+            optimizer: Optimizer::new(training.optimizer, training.momentum, training.weight_decay, &mlp),
+            mlp,
+            rng,
+            best: None,
+            evaluations_since_best: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+/// Mini-batch training on the samples in a new random order every epoch, continuing from `state`. With validation
+/// samples, the network with the lowest validation error is saved to `BEST_PATH` whenever it improves. The file starts
+/// out holding the best network of `state`, or is removed without one, so it never holds a network of an earlier run.
+/// Every epoch is added to `curve`, with the CIEDE2000 statistics of the validation samples in the outputs'
+/// `color_encoding`, and `checkpoint` is called with the state every `checkpoint_interval` epochs.
+pub fn train(
+    state: &mut State,
+    data: &Samples,
+    validation: Option<&Samples>,
+    training: &Training,
+    color_encoding: &str,
+    mut curve: Option<Curve>,
+    mut checkpoint: impl FnMut(&State),
+) {
+    let mut indices = Vec::with_capacity(data.len());
+    let instant = Instant::now();
+    let elapsed_before = state.elapsed;
+
+    match &state.best {
+        Some((_, _, best)) => fs::write(BEST_PATH, best.to_json()).unwrap(),
+        None if Path::new(BEST_PATH).exists() => fs::remove_file(BEST_PATH).unwrap(),
+        None => {},
+    }
+
+    while state.epoch < training.epochs {
+        let epoch = state.epoch;
+        let rate = training.schedule.rate(training.learning_rate, epoch, training.epochs);
+        // The order is shuffled from scratch so that it only depends on the rng, which checkpoints store.
+        indices.clear();
+        indices.extend(0..data.len());
+        indices.shuffle(&mut state.rng);
+
+        let mut error_sum = 0.0;
+        for batch in indices.chunks(training.batch_size) {
+            let (gradient, batch_error) = state.mlp.gradient(data, batch, &training.loss, training.threads);
+            state.optimizer.step(&mut state.mlp, &gradient, rate);
+            error_sum += batch_error;
+        }
+        let error = error_sum / data.len().max(1) as f64;
+        state.epoch += 1;
+        state.elapsed = elapsed_before + instant.elapsed();
+
+        if training.log_interval > 0 && state.epoch.is_multiple_of(training.log_interval) {
+            println!("Epoch {}: error {}, learning rate {}", state.epoch, error, rate);
+        }
+
+        let mut point = Point::new(state.epoch, state.elapsed.as_secs_f64(), rate, error);
+        let mut stop = false;
+        if let Some(validation) = validation.filter(|_| state.epoch.is_multiple_of(training.validation_interval)) {
+            let validation_error = state.mlp.error(validation, &training.loss);
+            // The other statistics are only needed for the curve.
+            if curve.is_some() {
+                let validation_mse = match training.loss {
+                    Loss::Mse => validation_error,
+                    _ => state.mlp.error(validation, &Loss::Mse),
+                };
+                let evaluation = Evaluation::new(&state.mlp, validation, color_encoding);
+                println!(
+                    "Epoch {}: validation error {}, mean CIEDE2000 error {:.3}",
+                    state.epoch, validation_error, evaluation.mean,
+                );
+                point.set_validation(validation_error, validation_mse, &evaluation);
+            } else {
+                println!("Epoch {}: validation error {}", state.epoch, validation_error);
+            }
+
+            if state.best.as_ref().is_none_or(|(_, best_error, _)| validation_error < *best_error) {
+                fs::write(BEST_PATH, state.mlp.to_json()).unwrap();
+                state.best = Some((state.epoch, validation_error, state.mlp.clone()));
+                state.evaluations_since_best = 0;
+            } else {
+                state.evaluations_since_best += 1;
+                if training.patience > 0 && state.evaluations_since_best >= training.patience {
+                    println!("Stopping early, the validation error hasn't improved for {} evaluations.", state.evaluations_since_best);
+                    stop = true;
+                }
+            }
+        }
+
+        if let Some(curve) = &mut curve {
+            curve.write(&point);
+        }
+
+        match training.halt_condition {
+            HaltCondition::Loss(target_loss) if error <= target_loss => {
+                println!("Reached the target error after {} epochs.", state.epoch);
+                stop = true;
+            },
+            HaltCondition::Time(time_limit) if state.elapsed >= time_limit => {
+                println!("Reached the time limit after {} epochs.", state.epoch);
+                stop = true;
+            },
+            _ => (),
+        }
+
+        if stop {
+            break;
+        }
+        if training.checkpoint_interval > 0 && state.epoch.is_multiple_of(training.checkpoint_interval) {
+            checkpoint(state);
+        }
+    }
+
+    if let Some((epoch, validation_error, _)) = &state.best {
+        println!("Lowest validation error: {} after {} epochs, saved to \"{}\".", validation_error, epoch, BEST_PATH);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    #[test]
+    fn resumed_runs_match_uninterrupted_ones() {
+        let mut config = Ini::new();
+        for (key, value) in [("batch_size", "8"), ("log_interval", "0"), ("threads", "1"), ("optimizer", "adam"), ("momentum", "0.9")] {
+            config.set("training", key, Some(value.to_string()));
+        }
+        config.set("general", "epochs", Some("6".to_string()));
+        let training = Training::from_config(&config, "srgb");
+
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(5);
+        let data = Samples::from_values((0..50 * 10).map(|_| rng.gen()).collect());
+        let mlp = Mlp::new(&[7, 6, 3], "srgb", &mut rng);
+
+        let mut uninterrupted = State::new(mlp, &training, rng);
+        let mut checkpoint = None;
+        train(&mut uninterrupted, &data, None, &training, "srgb", None, |state| {
+            if state.epoch == 2 {
+                checkpoint = Some(serde_json::to_string(state).unwrap());
+            }
+        });
+
+        // The thread count may change between the interrupted and the resumed run.
+        let mut resumed: State = serde_json::from_str(&checkpoint.unwrap()).unwrap();
+        train(&mut resumed, &data, None, &Training { threads: 3, ..training }, "srgb", None, |_| {});
+
+        assert_eq!(resumed.epoch, 6);
+        assert_eq!(resumed.mlp.layers(), uninterrupted.mlp.layers());
+    }
+}
@@ -0,0 +1,106 @@
+use configparser::ini::Ini;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::color::*;
+use crate::metadata::{file_sha256, ColorEncoding};
+use crate::network::Network;
+use crate::sampler::Sampler;
+use crate::settings::setting;
+use crate::solver::{cost, search};
+
+/// How the error of the network is estimated for a candidate input.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    /// The solver cost of the predicted color relative to the endpoint distance, without solving.
+    Proxy,
+    /// The CIEDE2000 distance between the predicted color and the result of a low quality search.
+    QuickSolve { iteration_count: i32, min_width: i32 },
+}
+
+/// Spends the solver budget on the inputs where an existing network is worst.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ActiveLearning {
+    pub nn_file: String,
+    pub nn_sha256: String,
+    pub candidate_count: usize,
+    pub scoring: Scoring,
+}
+
+impl ActiveLearning {
+    pub fn from_config(config: &Ini) -> Option<ActiveLearning> {
+        if !setting(config, "active_learning", "enabled", false) {
+            return None;
+        }
+
+        let nn_file = setting(config, "active_learning", "nn_file", "nn.json".to_string());
+        let candidate_count = setting(config, "active_learning", "candidate_count", 16usize);
+        if candidate_count == 0 {
+            panic!("At least one candidate is needed per sample.");
+        }
+        let scoring = match setting(config, "active_learning", "scoring", "quick_solve".to_string()).as_str() {
+            "proxy" => Scoring::Proxy,
+            "quick_solve" => Scoring::QuickSolve {
+                iteration_count: setting(config, "active_learning", "quick_iteration_count", 2),
+                min_width: setting(config, "active_learning", "quick_min_width", 4),
+            },
+            scoring => panic!("Unknown scoring \"{}\".", scoring),
+        };
+
+        Some(ActiveLearning {
+            nn_sha256: file_sha256(&nn_file),
+            nn_file,
+            candidate_count,
+            scoring,
+        })
+    }
+}
+
+pub struct Selector {
+    settings: ActiveLearning,
+    network: Network,
+}
+
+impl Selector {
+    pub fn new(settings: ActiveLearning) -> Selector {
+        // The candidates are given to the network in sRGB.
+        let network = Network::load(&settings.nn_file, ColorEncoding::Srgb);
+        Selector { settings, network }
+    }
+
+    /// Draws the candidates from the sampler and returns the one with the largest estimated error.
+    pub fn select(&self, sampler: &mut Sampler, rng: &mut impl Rng) -> ([f64; 3], [f64; 3], f64) {
+        let mut best = sampler.sample(rng);
+        let mut best_score = self.score(&best, rng);
+        for _ in 1..self.settings.candidate_count {
+            let candidate = sampler.sample(rng);
+            let score = self.score(&candidate, rng);
+
+            if score > best_score {
+                best = candidate;
+                best_score = score;
+            }
+        }
+        best
+    }
+
+    fn score(&self, (input_c1, input_c2, input_i): &([f64; 3], [f64; 3], f64), rng: &mut impl Rng) -> f64 {
+        let mut inputs = rgb_to_srgb(&cielab_to_rgb(input_c1)).to_vec();
+        inputs.extend(rgb_to_srgb(&cielab_to_rgb(input_c2)));
+        inputs.push(*input_i);
+
+        let prediction = self.network.run(&inputs);
+        let prediction = rgb_to_cielab(&srgb_to_rgb(&[prediction[0], prediction[1], prediction[2]]));
+
+        match self.settings.scoring {
+            Scoring::Proxy => {
+                let distance = cielab_dist_ciede2000(input_c1, input_c2).max(1.0);
+                cost(input_c1, input_c2, *input_i, &prediction) / distance
+            },
+            Scoring::QuickSolve { iteration_count, min_width } => {
+                let output_c = search(input_c1, input_c2, *input_i, iteration_count, min_width, rng);
+                cielab_dist_ciede2000(&prediction, &output_c)
+            },
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Augmentation {
+    /// Also write f(c2, c1, 1 - t) = f(c1, c2, t) for every solved sample.
+    pub mirror: bool,
+    /// Fraction of the samples whose mirrored input is solved as well to check the symmetry.
+    pub verify_fraction: f64,
+}
+
+/// CIEDE2000 distances between solved samples and the solutions of their mirrored inputs.
+pub struct SymmetryCheck {
+    tolerance: f64,
+    count: u32,
+    sum: f64,
+    max: f64,
+    above_tolerance: u32,
+}
+
+impl SymmetryCheck {
+    pub fn new(tolerance: f64) -> SymmetryCheck {
+        SymmetryCheck {
+            tolerance,
+            count: 0,
+            sum: 0.0,
+            max: 0.0,
+            above_tolerance: 0,
+        }
+    }
+
+    pub fn add(&mut self, de: f64) {
+        self.count += 1;
+        self.sum += de;
+        self.max = self.max.max(de);
+        if de > self.tolerance {
+            self.above_tolerance += 1;
+        }
+    }
+
+    pub fn report(&self) {
+        if self.count == 0 {
+            return;
+        }
+
+        println!("Symmetry check of {} samples:", self.count);
+        println!("\tMean distance: {:.4}", self.sum / self.count as f64);
+        println!("\tMax distance: {:.4}", self.max);
+        println!("\tAbove {}: {} ({:.2}%)", self.tolerance, self.above_tolerance, 100.0 * self.above_tolerance as f64 / self.count as f64);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+use crate::active::ActiveLearning;
+use crate::augmentation::Augmentation;
+use crate::dataset::Encoding;
+use crate::metadata::ColorEncoding;
+use crate::sampler::Sampling;
+use crate::sequence::Sequence;
+
+/// Everything needed to continue an interrupted run exactly where it was flushed.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub sample_count: i32,
+    pub iteration_count: i32,
+    pub min_width: i32,
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub augmentation: Augmentation,
+    #[serde(default)]
+    pub sequence: Option<Sequence>,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default)]
+    pub color_encoding: ColorEncoding,
+    #[serde(default)]
+    pub active_learning: Option<ActiveLearning>,
+    pub sample_idx: i32,
+    pub bytes_written: u64,
+    pub rng: Xoshiro256PlusPlus,
+    #[serde(default)]
+    pub sampler_state: [u64; 3],
+}
+
+impl Checkpoint {
+    pub fn path(dir: &Path, seed: u64) -> String {
+        dir.join(format!("data_{}.checkpoint.json", seed)).to_string_lossy().to_string()
+    }
+
+    pub fn load(dir: &Path, seed: u64) -> Checkpoint {
+        let path = Checkpoint::path(dir, seed);
+        let json = fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        serde_json::from_str(&json).expect("Found an invalid checkpoint.")
+    }
+
+    /// Written to a temporary file first so that an interruption never leaves a truncated checkpoint.
+    pub fn save(&self, dir: &Path) {
+        let path = Checkpoint::path(dir, self.seed);
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, serde_json::to_string(self).unwrap()).unwrap();
+        fs::rename(&tmp_path, &path).unwrap();
+    }
+}
@@ -1,12 +1,16 @@
 use std::f64::consts::PI;
 use rand::Rng;
 
+/// Bounding box of the CIELAB colors that are inside the sRGB gamut.
+pub const CIELAB_MIN: [f64; 3] = [0.0, -86.18704166062818, -107.86309588218504];
+pub const CIELAB_MAX: [f64; 3] = [100.0, 98.25137200280926, 94.48279336975611];
+
 pub fn random_cielab(rng: &mut impl Rng) -> [f64; 3] {
     loop {
         let c = [
-            rng.gen_range(0.0..=100.0),
-            rng.gen_range(-86.18704166062818..=98.25137200280926),
-            rng.gen_range(-107.86309588218504..=94.48279336975611),
+            rng.gen_range(CIELAB_MIN[0]..=CIELAB_MAX[0]),
+            rng.gen_range(CIELAB_MIN[1]..=CIELAB_MAX[1]),
+            rng.gen_range(CIELAB_MIN[2]..=CIELAB_MAX[2]),
         ];
 
         if is_valid_rgb(&cielab_to_rgb(&c)) {
@@ -16,10 +20,10 @@ pub fn random_cielab(rng: &mut impl Rng) -> [f64; 3] {
 }
 
 pub fn is_valid_cielab(c: &[f64; 3]) -> bool {
-    if c[0] < 0.0 || c[0] > 100.0 || c[1] < -86.18704166062818 || c[1] > 98.25137200280926 || c[2] < -107.86309588218504 || c[2] > 94.48279336975611 {
+    if c[0] < CIELAB_MIN[0] || c[0] > CIELAB_MAX[0] || c[1] < CIELAB_MIN[1] || c[1] > CIELAB_MAX[1] || c[2] < CIELAB_MIN[2] || c[2] > CIELAB_MAX[2] {
         false
     } else {
-        is_valid_rgb(&cielab_to_rgb(&c))
+        is_valid_rgb(&cielab_to_rgb(c))
     }
 }
 
@@ -46,6 +50,17 @@ pub fn rgb_to_srgb(c: &[f64; 3]) -> [f64; 3] {
     output
 }
 
+pub fn srgb_to_rgb(c: &[f64; 3]) -> [f64; 3] {
+    let mut output = [0.0; 3];
+    for (c_lin, c_s_rgb) in output.iter_mut().zip(c.iter()) {
+        *c_lin = if *c_s_rgb <= 0.04045 {
+            c_s_rgb / 12.92
+        } else {
+            ((c_s_rgb + 0.055) / 1.055).powf(2.4)
+        }
+    }
+    output
+}
 
 fn from_ciexyz(c: &[f64; 3]) -> [f64; 3] {
     [
@@ -55,12 +70,31 @@ fn from_ciexyz(c: &[f64; 3]) -> [f64; 3] {
     ]
 }
 
+fn to_ciexyz(c: &[f64; 3]) -> [f64; 3] {
+    [
+        0.4124 * c[0] + 0.3576 * c[1] + 0.1805 * c[2],
+        0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2],
+        0.0193 * c[0] + 0.1192 * c[1] + 0.9505 * c[2]
+    ]
+}
 
 pub fn cielab_to_rgb(c: &[f64; 3]) -> [f64; 3] {
     let c = cielab_to_ciexyz(c);
     from_ciexyz(&[c[0] / 100.0, c[1] / 100.0, c[2] / 100.0])
 }
 
+pub fn rgb_to_cielab(c: &[f64; 3]) -> [f64; 3] {
+    let c = to_ciexyz(c);
+    ciexyz_to_cielab(&[c[0] * 100.0, c[1] * 100.0, c[2] * 100.0])
+}
+
+fn cie_f(t: f64) -> f64 {
+    if t > 216.0 / 24389.0 {
+        t.powf(1.0 / 3.0)
+    } else {
+        841.0 * t / 108.0 + 4.0 / 29.0
+    }
+}
 
 fn cie_f_inverse(t: f64) -> f64 {
     let delta = 6.0 / 29.0;
@@ -77,15 +111,27 @@ fn cielab_to_ciexyz(c: &[f64; 3]) -> [f64; 3] {
     let yn = 100.0;
     let zn = 108.8840;
 
-    let foo = (c[0] + 16.0) / 116.0;
+    let fy = (c[0] + 16.0) / 116.0;
+
+    [
+        xn * cie_f_inverse(fy + c[1] / 500.0),
+        yn * cie_f_inverse(fy),
+        zn * cie_f_inverse(fy - c[2] / 200.0),
+    ]
+}
 
+fn ciexyz_to_cielab(c: &[f64; 3]) -> [f64; 3] {
     [
-        xn * cie_f_inverse(foo + c[1] / 500.0),
-        yn * cie_f_inverse(foo),
-        zn * cie_f_inverse(foo - c[2] / 200.0),
+        116.0 * cie_f(c[1] / 100.0) - 16.0,
+        500.0 * (cie_f(c[0] / 95.0489) - cie_f(c[1] / 100.0)),
+        200.0 * (cie_f(c[1] / 100.0) - cie_f(c[2] / 108.8840))
     ]
 }
 
+pub fn cielab_to_cielch(c: &[f64; 3]) -> [f64; 3] {
+    [c[0], c[1].hypot(c[2]), cie_atan2(c[2], c[1])]
+}
+
 fn cie_atan2(y: f64, x: f64) -> f64 {
     (y.atan2(x) / (2.0 * PI)).rem_euclid(1.0) * 360.0
 }
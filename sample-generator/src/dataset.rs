@@ -0,0 +1,483 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::color::*;
+use crate::metadata::{column_names, ColorEncoding, Metadata};
+
+/// CSV datasets start with a line of column names. Binary datasets start with a header of `MAGIC`, the format version,
+/// the number of bytes per value and the number of columns, each as a little-endian `u32`, followed by the rows as
+/// little-endian floats, except for the pair id of `f32` sequence datasets, which is a little-endian `u32`. The row
+/// count follows from the file length so that the file can be appended to.
+pub const MAGIC: &[u8; 4] = b"CGDS";
+pub const VERSION: u32 = 1;
+pub const HEADER_LEN: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Csv,
+    Binary,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Binary => "bin",
+        }
+    }
+
+    pub fn from_path(path: &str) -> Format {
+        if path.ends_with(".bin") {
+            Format::Binary
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!("Unknown dataset format \"{}\".", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+impl Precision {
+    /// The precision of a dataset file, `F64` for CSV datasets.
+    pub fn of(path: &str) -> Precision {
+        if Format::from_path(path) == Format::Csv {
+            return Precision::F64;
+        }
+
+        let mut header = [0; HEADER_LEN];
+        File::open(path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        match parse_header(&header).0 {
+            4 => Precision::F32,
+            _ => Precision::F64,
+        }
+    }
+
+    pub fn value_len(&self) -> usize {
+        match self {
+            Precision::F32 => 4,
+            Precision::F64 => 8,
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(Precision::F32),
+            "f64" => Ok(Precision::F64),
+            _ => Err(format!("Unknown precision \"{}\".", s)),
+        }
+    }
+}
+
+/// How the rows of a dataset are written. `precision` only applies to binary datasets.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Encoding {
+    pub format: Format,
+    pub precision: Precision,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding {
+            format: Format::Csv,
+            precision: Precision::F64,
+        }
+    }
+}
+
+impl Encoding {
+    pub fn header(&self, names: &[String]) -> Vec<u8> {
+        match self.format {
+            Format::Csv => format!("{}\n", names.join(", ")).into_bytes(),
+            Format::Binary => {
+                let mut header = MAGIC.to_vec();
+                header.extend_from_slice(&VERSION.to_le_bytes());
+                header.extend_from_slice(&(self.precision.value_len() as u32).to_le_bytes());
+                header.extend_from_slice(&(names.len() as u32).to_le_bytes());
+                header
+            },
+        }
+    }
+
+    /// The pair id of sequence rows is stored as a `u32` in `f32` datasets, where floats only hold integers up to 2^24
+    /// exactly.
+    pub fn encode_row(&self, values: &[f64], has_pair_id: bool, buffer: &mut Vec<u8>) {
+        match self.format {
+            Format::Csv => {
+                let row = values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+                buffer.extend_from_slice(row.as_bytes());
+                buffer.push(b'\n');
+            },
+            Format::Binary => {
+                for (idx, v) in values.iter().enumerate() {
+                    match self.precision {
+                        Precision::F32 if has_pair_id && idx == 0 => buffer.extend_from_slice(&(*v as u32).to_le_bytes()),
+                        Precision::F32 => buffer.extend_from_slice(&(*v as f32).to_le_bytes()),
+                        Precision::F64 => buffer.extend_from_slice(&v.to_le_bytes()),
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// The rows of a dataset, stored contiguously.
+pub struct Dataset {
+    pub columns: usize,
+    pub names: Vec<String>,
+    pub values: Vec<f64>,
+}
+
+impl Dataset {
+    pub fn read(path: &str) -> Dataset {
+        match Format::from_path(path) {
+            Format::Csv => Dataset::read_csv(path),
+            Format::Binary => Dataset::read_binary(path),
+        }
+    }
+
+    /// Datasets written before CSV headers were added start with the first row.
+    fn read_csv(path: &str) -> Dataset {
+        let buffered = BufReader::new(File::open(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path)));
+
+        let mut columns = 0;
+        let mut names = None;
+        let mut values = Vec::new();
+        for line in buffered.lines() {
+            let line = line.unwrap();
+            if line.is_empty() {
+                continue;
+            }
+            if columns == 0 && names.is_none() && line.split(", ").any(|s| s.parse::<f64>().is_err()) {
+                let header = line.split(", ").map(String::from).collect::<Vec<_>>();
+                columns = header.len();
+                names = Some(header);
+                continue;
+            }
+
+            let row = line.split(", ").map(|s| s.parse::<f64>().expect("Found an invalid sample.")).collect::<Vec<_>>();
+            if columns == 0 {
+                columns = row.len();
+            } else if row.len() != columns {
+                panic!("Found an invalid sample.");
+            }
+            values.extend(row);
+        }
+
+        let names = names.unwrap_or_else(|| stored_names(path, columns).unwrap_or_else(|error| panic!("{}", error)));
+        Dataset { columns, names, values }
+    }
+
+    fn read_binary(path: &str) -> Dataset {
+        let bytes = fs::read(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        let (value_len, columns) = parse_header(&bytes);
+        let names = stored_names(path, columns).unwrap_or_else(|error| panic!("{}", error));
+        let has_pair_id = names.first().is_some_and(|name| name == "pair_id");
+
+        let values = bytes[HEADER_LEN..]
+            .chunks_exact(value_len)
+            .enumerate()
+            .map(|(idx, v)| match value_len {
+                4 if has_pair_id && idx % columns == 0 => u32::from_le_bytes(v.try_into().unwrap()) as f64,
+                4 => f32::from_le_bytes(v.try_into().unwrap()) as f64,
+                _ => f64::from_le_bytes(v.try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+        if values.len() % columns != 0 {
+            panic!("The file \"{}\" ends with an incomplete sample.", path);
+        }
+
+        Dataset { columns, names, values }
+    }
+
+    pub fn from_rows<'a>(names: Vec<String>, rows: impl IntoIterator<Item = &'a [f64]>) -> Dataset {
+        Dataset {
+            columns: names.len(),
+            names,
+            values: rows.into_iter().flatten().copied().collect(),
+        }
+    }
+
+    /// Sequence datasets start every row with the id of the gradient it belongs to.
+    pub fn has_pair_id(&self) -> bool {
+        self.names.first().is_some_and(|name| name == "pair_id")
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Columns of the endpoints, in every encoded color space, and the interpolant.
+    pub fn input_columns(&self) -> Range<usize> {
+        let t = self.column("t").expect("The dataset has no interpolant column.");
+        self.has_pair_id() as usize..t + 1
+    }
+
+    pub fn interpolant(&self, row: &[f64]) -> f64 {
+        row[self.column("t").expect("The dataset has no interpolant column.")]
+    }
+
+    /// The CIELAB color of `prefix` ("c1", "c2" or "out") in a row, taken from the most precise encoding present.
+    pub fn cielab(&self, row: &[f64], prefix: &str) -> [f64; 3] {
+        let channels = |encoding: ColorEncoding| {
+            let columns = encoding.channels().iter().map(|channel| self.column(&format!("{}_{}", prefix, channel))).collect::<Option<Vec<_>>>()?;
+            Some([row[columns[0]], row[columns[1]], row[columns[2]]])
+        };
+
+        if let Some(c) = channels(ColorEncoding::Lab) {
+            c
+        } else if let Some(c) = channels(ColorEncoding::LinearRgb) {
+            rgb_to_cielab(&c)
+        } else if let Some(c) = channels(ColorEncoding::Srgb) {
+            rgb_to_cielab(&srgb_to_rgb(&c))
+        } else {
+            panic!("The dataset has no \"{}\" color columns.", prefix);
+        }
+    }
+
+    /// The endpoints in sRGB and the interpolant, whatever the color encoding of the dataset, for neighbour queries.
+    pub fn input_point(&self, row: &[f64]) -> [f64; 7] {
+        let c1 = rgb_to_srgb(&cielab_to_rgb(&self.cielab(row, "c1")));
+        let c2 = rgb_to_srgb(&cielab_to_rgb(&self.cielab(row, "c2")));
+        [c1[0], c1[1], c1[2], c2[0], c2[1], c2[2], self.interpolant(row)]
+    }
+
+    /// Replaces the color of `prefix` in every encoding present in the row.
+    pub fn set_cielab(&self, row: &mut [f64], prefix: &str, c: &[f64; 3]) {
+        let all = ColorEncoding::All;
+        for (channel, v) in all.channels().iter().zip(all.encode(c)) {
+            if let Some(idx) = self.column(&format!("{}_{}", prefix, channel)) {
+                row[idx] = v;
+            }
+        }
+    }
+
+    /// Ranges of the rows that belong to the same gradient in sequence datasets, single rows otherwise. Mirrored rows,
+    /// written right after their originals, join the group of the original so that no command separates them.
+    pub fn groups(&self) -> Vec<Range<usize>> {
+        let mut groups: Vec<Range<usize>> = Vec::new();
+        for (idx, row) in self.rows().enumerate() {
+            match groups.last_mut() {
+                Some(group) if self.same_gradient(self.row(group.start), row) => group.end = idx + 1,
+                _ => groups.push(idx..idx + 1),
+            }
+        }
+        groups
+    }
+
+    /// Whether `b` has the pair id of `a` or its endpoints swapped, like a mirrored row.
+    fn same_gradient(&self, a: &[f64], b: &[f64]) -> bool {
+        if self.has_pair_id() && a[0] == b[0] {
+            return true;
+        }
+        self.cielab(a, "c1") == self.cielab(b, "c2") && self.cielab(a, "c2") == self.cielab(b, "c1")
+    }
+
+    pub fn row(&self, idx: usize) -> &[f64] {
+        &self.values[idx * self.columns..(idx + 1) * self.columns]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f64]> {
+        self.values.chunks_exact(self.columns.max(1))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() / self.columns.max(1)
+    }
+
+    pub fn write(&self, path: &str, encoding: Encoding) {
+        let mut buffer = encoding.header(&self.names);
+        for row in self.rows() {
+            encoding.encode_row(row, self.has_pair_id(), &mut buffer);
+        }
+        fs::write(path, buffer).unwrap();
+    }
+}
+
+/// Column names of datasets without a CSV header, from their metadata or, for datasets written before the color encoding
+/// was configurable, from the column count.
+fn stored_names(path: &str, columns: usize) -> Result<Vec<String>, String> {
+    if let Some(metadata) = Metadata::load(path) {
+        if metadata.columns.len() != columns {
+            return Err(format!("The dataset \"{}\" has {} columns but its metadata lists {}.", path, columns, metadata.columns.len()));
+        }
+        return Ok(metadata.columns);
+    }
+
+    match columns {
+        10 => Ok(column_names(false, ColorEncoding::Srgb)),
+        11 => Ok(column_names(true, ColorEncoding::Srgb)),
+        _ => Err(format!("The columns of \"{}\" are unknown.", path)),
+    }
+}
+
+/// Returns the number of bytes per value and the number of columns.
+fn parse_header(bytes: &[u8]) -> (usize, usize) {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+        panic!("Found an invalid binary dataset header.");
+    }
+
+    let field = |idx: usize| u32::from_le_bytes(bytes[idx * 4..idx * 4 + 4].try_into().unwrap()) as usize;
+    if field(1) != VERSION as usize {
+        panic!("Unsupported binary dataset version {}.", field(1));
+    }
+    if (field(2) != 4 && field(2) != 8) || field(3) == 0 {
+        panic!("Found an invalid binary dataset header.");
+    }
+
+    (field(2), field(3))
+}
+
+/// `sample-generator convert <input> <output> [f32|f64]`, the formats follow from the file extensions.
+pub fn convert(args: &[String]) {
+    if args.len() < 2 {
+        panic!("Usage: convert <input> <output> [f32|f64]");
+    }
+
+    let encoding = Encoding {
+        format: Format::from_path(&args[1]),
+        precision: args.get(2).map_or(Precision::F64, |precision| precision.parse().unwrap()),
+    };
+
+    let dataset = Dataset::read(&args[0]);
+    dataset.write(&args[1], encoding);
+
+    Metadata::save_derived(&args[0], &args[1], &format!("convert {}", args.join(" ")), &dataset, encoding);
+
+    println!("Converted {} samples.", dataset.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pair_id: Option<f64>, c1: [f64; 3], c2: [f64; 3], t: f64) -> Vec<f64> {
+        let mut row = pair_id.into_iter().collect::<Vec<_>>();
+        row.extend(c1);
+        row.extend(c2);
+        row.push(t);
+        row.extend([0.5; 3]);
+        row
+    }
+
+    #[test]
+    fn mirrored_rows_join_their_originals() {
+        let (a, b, c) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7], [0.4, 0.4, 0.4]);
+        let rows = [row(None, a, b, 0.25), row(None, b, a, 0.75), row(None, a, c, 0.5), row(None, c, b, 0.5), row(None, b, c, 0.5)];
+        let dataset = Dataset::from_rows(column_names(false, ColorEncoding::Srgb), rows.iter().map(Vec::as_slice));
+
+        assert_eq!(dataset.groups(), vec![0..2, 2..3, 3..5]);
+    }
+
+    #[test]
+    fn mirrored_sequences_join_their_originals() {
+        let (a, b, c) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7], [0.4, 0.4, 0.4]);
+        let rows = [
+            row(Some(0.0), a, b, 0.0), row(Some(0.0), a, b, 1.0), row(Some(0.0), b, a, 0.0), row(Some(0.0), b, a, 1.0),
+            // Written with their own pair id before mirrored rows shared it.
+            row(Some(1.0), a, c, 0.0), row(Some(1.0), a, c, 1.0), row(Some(2.0), c, a, 0.0), row(Some(2.0), c, a, 1.0),
+            row(Some(3.0), b, c, 0.0), row(Some(3.0), b, c, 1.0),
+        ];
+        let dataset = Dataset::from_rows(column_names(true, ColorEncoding::Srgb), rows.iter().map(Vec::as_slice));
+
+        assert_eq!(dataset.groups(), vec![0..4, 4..8, 8..10]);
+    }
+
+    #[test]
+    fn binary_datasets_roundtrip() {
+        let (a, b) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7]);
+        let rows = [row(None, a, b, 0.25), row(None, b, a, 1.0 / 3.0)];
+        let dataset = Dataset::from_rows(column_names(false, ColorEncoding::Srgb), rows.iter().map(Vec::as_slice));
+
+        for precision in [Precision::F32, Precision::F64] {
+            let path = std::env::temp_dir().join(format!("sample_generator_roundtrip_{:?}.bin", precision)).to_string_lossy().to_string();
+            dataset.write(&path, Encoding { format: Format::Binary, precision });
+            let read = Dataset::read(&path);
+            let len = fs::metadata(&path).unwrap().len() as usize;
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(len, HEADER_LEN + dataset.values.len() * precision.value_len());
+            assert_eq!(read.names, dataset.names);
+            for (read, written) in read.values.iter().zip(dataset.values.iter()) {
+                match precision {
+                    Precision::F32 => assert_eq!(*read, *written as f32 as f64),
+                    Precision::F64 => assert_eq!(read, written),
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid binary dataset header")]
+    fn binary_headers_need_columns() {
+        let mut header = Encoding { format: Format::Binary, precision: Precision::F64 }.header(&[]);
+        header.extend_from_slice(&[0; 8]);
+        parse_header(&header);
+    }
+
+    #[test]
+    fn f32_datasets_keep_large_pair_ids() {
+        let (a, b) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7]);
+        let pair_id = (1 << 24) + 1;
+        let rows = [row(Some(pair_id as f64), a, b, 0.25), row(Some((pair_id + 1) as f64), a, a, 0.5)];
+        let dataset = Dataset::from_rows(column_names(true, ColorEncoding::Srgb), rows.iter().map(Vec::as_slice));
+
+        let path = std::env::temp_dir().join("sample_generator_pair_ids.bin").to_string_lossy().to_string();
+        dataset.write(&path, Encoding { format: Format::Binary, precision: Precision::F32 });
+        let read = Dataset::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.row(0)[0], pair_id as f64);
+        assert_eq!(read.row(1)[0], (pair_id + 1) as f64);
+        assert_eq!(read.groups(), vec![0..1, 1..2]);
+    }
+
+    #[test]
+    fn converted_datasets_keep_their_column_names() {
+        let rows = [[20.0, 10.0, -5.0, 60.0, -30.0, 40.0, 0.25, 30.0, 0.0, 5.0]];
+        let dataset = Dataset::from_rows(column_names(false, ColorEncoding::Lab), rows.iter().map(|row| row.as_slice()));
+
+        // A CSV file with a header but without metadata, e.g. written by another tool.
+        let path = |name: &str| std::env::temp_dir().join(name).to_string_lossy().to_string();
+        let (input, output) = (path("sample_generator_lab.csv"), path("sample_generator_lab.bin"));
+        dataset.write(&input, Encoding::default());
+        let _ = fs::remove_file(Metadata::path(&input));
+
+        convert(&[input.clone(), output.clone()]);
+        let read = Dataset::read(&output);
+        let metadata = Metadata::load(&output).unwrap();
+        for path in [&input, &output, &Metadata::path(&output)] {
+            fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(read.names, dataset.names);
+        assert_eq!(read.values, dataset.values);
+        assert_eq!(metadata.color_encoding, ColorEncoding::Lab);
+    }
+}
@@ -1,150 +1,426 @@
+use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::Write;
-use std::time::Instant;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
 use configparser::ini::Ini;
 use rand::{Rng, SeedableRng};
+use crate::active::{ActiveLearning, Selector};
+use crate::augmentation::{Augmentation, SymmetryCheck};
+use crate::checkpoint::Checkpoint;
 use crate::color::*;
+use crate::dataset::{Encoding, Format, Precision};
+use crate::metadata::{column_names, file_sha256, ColorEncoding, Metadata};
+use crate::progress::Progress;
+use crate::region::Region;
+use crate::sampler::{EndpointDistribution, InterpolantDistribution, Sampler, Sampling};
+use crate::sequence::{Sequence, MAX_ATTEMPTS};
+use crate::settings::setting;
+use crate::solver::solve;
 
+mod active;
+mod augmentation;
+mod checkpoint;
 mod color;
+mod dataset;
+mod metadata;
+mod network;
+mod neighbours;
+mod outliers;
+mod progress;
+mod region;
+mod report;
+mod sampler;
+mod sequence;
+mod settings;
+mod solver;
+mod toolbox;
 
-fn cost(ic1: &[f64; 3], ic2: &[f64; 3], ii: f64, oc: &[f64; 3]) -> f64 {
-    let de1 = cielab_dist_ciede2000(&ic1, &oc);
-    let de2 = cielab_dist_ciede2000(&ic2, &oc);
-    let oi = de1 / (de1 + de2);
+fn seed_setting(config: &Ini, args: &[String]) -> u64 {
+    let seed = match args.iter().position(|arg| arg == "--seed") {
+        Some(idx) => Some(args.get(idx + 1).expect("\"--seed\" must be followed by a seed.").clone()),
+        None => config.get("general", "seed"),
+    };
 
-    (de1 + de2) * ((oi - ii).abs() + 0.5)
+    seed.as_deref().map_or_else(|| rand::thread_rng().gen(), parse_seed)
+}
+
+/// The seeds of all commands are either a non-negative integer or "random".
+fn parse_seed(seed: &str) -> u64 {
+    match seed {
+        "random" => rand::thread_rng().gen(),
+        seed => seed.parse().expect("The seed must be a non-negative integer or \"random\"."),
+    }
+}
+
+/// Sequence rows start with the id of the gradient they belong to. The colors are given in CIELAB and written in the
+/// spaces of the color encoding.
+fn row(pair_id: Option<i32>, input_c1: &[f64; 3], input_c2: &[f64; 3], input_i: f64, output_c: &[f64; 3], color_encoding: ColorEncoding) -> Vec<f64> {
+    let mut row = pair_id.map(|pair_id| vec![pair_id as f64]).unwrap_or_default();
+    row.extend(color_encoding.encode(input_c1));
+    row.extend(color_encoding.encode(input_c2));
+    row.push(input_i);
+    row.extend(color_encoding.encode(output_c));
+    row
+}
+
+fn data_path(dir: &Path, seed: u64, encoding: &Encoding) -> String {
+    dir.join(format!("data_{}.{}", seed, encoding.format.extension())).to_string_lossy().to_string()
 }
 
 fn main() {
-    let config_str = fs::read_to_string("config.ini").expect("Error while reading the configuration file.");
-    let mut config = Ini::new();
-    let _ = config.read(config_str);
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("convert") => dataset::convert(&args[2..]),
+        Some("merge") => toolbox::merge(&args[2..]),
+        Some("dedupe") => toolbox::dedupe(&args[2..]),
+        Some("shuffle") => toolbox::shuffle(&args[2..]),
+        Some("split") => toolbox::split(&args[2..]),
+        Some("report") => report::report(&args[2..]),
+        Some("outliers") => outliers::outliers(&args[2..]),
+        _ => {
+            let config_str = fs::read_to_string("config.ini").expect("Error while reading the configuration file.");
+            let mut config = Ini::new();
+            let _ = config.read(config_str);
+            generate(Path::new(""), &config, &args, None);
+        },
+    }
+}
+
+/// Generates the samples of `config` into `dir`. `interrupt_at` stops the run right after the rows of that many samples
+/// are written and before their checkpoint is, the worst moment for a run to be killed.
+fn generate(dir: &Path, config: &Ini, args: &[String], interrupt_at: Option<i32>) {
 
     let sample_count = config.getint("general", "sample_count").unwrap().unwrap() as i32;
     let iteration_count = config.getint("quality", "iteration_count").unwrap().unwrap() as i32;
     let min_width = config.getint("quality", "min_width").unwrap().unwrap() as i32;
+    let sampling = Sampling {
+        endpoints: setting(config, "sampling", "endpoints", EndpointDistribution::UniformLab),
+        interpolant: setting(config, "sampling", "interpolant", InterpolantDistribution::Uniform),
+        region: Region::from_config(config),
+        anchor_fraction: setting(config, "sampling", "anchor_fraction", 0.0),
+        near_endpoint_fraction: setting(config, "sampling", "near_endpoint_fraction", 0.0),
+        near_endpoint_width: setting(config, "sampling", "near_endpoint_width", 0.05),
+    };
+    if sampling.anchor_fraction < 0.0 || sampling.near_endpoint_fraction < 0.0 || sampling.anchor_fraction + sampling.near_endpoint_fraction > 1.0 {
+        panic!("The anchor and near endpoint fractions must be non-negative and add up to at most 1.");
+    }
+    if !(0.0..=0.5).contains(&sampling.near_endpoint_width) {
+        panic!("The near endpoint width must be between 0 and 0.5.");
+    }
+    let augmentation = Augmentation {
+        mirror: setting(config, "augmentation", "mirror", false),
+        verify_fraction: setting(config, "augmentation", "verify_fraction", 0.0),
+    };
+    let verify_tolerance = setting(config, "augmentation", "verify_tolerance", 1.0);
+    let sequence = Sequence::from_config(config);
+    let encoding = Encoding {
+        format: setting(config, "output", "format", Format::Csv),
+        precision: setting(config, "output", "precision", Precision::F64),
+    };
+    let color_encoding = setting(config, "output", "color_encoding", ColorEncoding::Srgb);
+    let active_learning = ActiveLearning::from_config(config);
+    let progress_interval = Duration::from_secs_f64(setting(config, "progress", "interval", 5.0));
+    let progress_log = setting(config, "progress", "log", false);
+    let columns = column_names(sequence.is_some(), color_encoding);
 
-    let seed = rand::thread_rng().gen();
-    println!("Seed: {}", seed);
-    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let resume_seed = args.iter().position(|arg| arg == "--resume").map(|idx| {
+        args.get(idx + 1)
+            .and_then(|seed| seed.parse::<u64>().ok())
+            .expect("\"--resume\" must be followed by the seed of the run to continue.")
+    });
 
-    let mut output = File::create(format!("data_{}.csv", seed)).unwrap();
+    let (seed, mut rng, mut sampler, mut sample_idx, mut bytes_written, mut output) = match resume_seed {
+        Some(seed) => {
+            let checkpoint = Checkpoint::load(dir, seed);
+            if checkpoint.sample_count != sample_count {
+                panic!("The sample count differs from the one used by the run being resumed.");
+            }
+            if checkpoint.iteration_count != iteration_count || checkpoint.min_width != min_width {
+                panic!("The quality configuration differs from the one used by the run being resumed.");
+            }
+            if checkpoint.sampling != sampling {
+                panic!("The sampling configuration differs from the one used by the run being resumed.");
+            }
+            if checkpoint.augmentation != augmentation {
+                panic!("The augmentation configuration differs from the one used by the run being resumed.");
+            }
+            if checkpoint.sequence != sequence {
+                panic!("The sequence configuration differs from the one used by the run being resumed.");
+            }
+            if checkpoint.encoding != encoding || checkpoint.color_encoding != color_encoding {
+                panic!("The output configuration differs from the one used by the run being resumed.");
+            }
+            if checkpoint.active_learning != active_learning {
+                panic!("The active learning configuration or network differs from the one used by the run being resumed.");
+            }
 
-    println!("Generating samples...");
+            let mut sampler = Sampler::new(sampling.clone(), seed);
+            sampler.restore(checkpoint.sampler_state);
 
-    let instant = Instant::now();
+            let mut output = OpenOptions::new().write(true).open(data_path(dir, seed, &encoding)).unwrap();
+            output.set_len(checkpoint.bytes_written).unwrap();
+            output.seek(SeekFrom::End(0)).unwrap();
 
-    let mut str = String::new();
-    let mut sample_idx = 0;
-    'outer_loop:
-        while sample_idx < sample_count {
-            let input_c1 = random_cielab(&mut rng);
-            let input_c2 = random_cielab(&mut rng);
-            let input_i: f64 = rng.gen();
-
-            let width = min_width + rng.gen_range(0..=5);
-            let mut span = 210.0 + 5.0 * rng.gen::<f64>();
-            let mut increment = span / width as f64;
-            let mut min_l = -54.5 + rng.gen::<f64>();
-            let mut min_a = -99.5 + rng.gen::<f64>();
-            let mut min_b = -109.5 + rng.gen::<f64>();
-            let mut min_cost = f64::INFINITY;
-            let mut min_cost_oc = [0.0; 3];
-            for iteration in 0..iteration_count {
-                if iteration == 0 {
-                    for pl in 0..(width * 4) {
-                        let l = min_l + (pl as f64 + 0.5) * (increment / 4.0);
-
-                        for pa in 0..(width * 4) {
-                            let a = min_a + (pa as f64 + 0.5) * (increment / 4.0);
-
-                            for pb in 0..(width * 4) {
-                                let b = min_b + (pb as f64 + 0.5) * (increment / 4.0);
-
-                                let candidate_c = [l, a, b];
-
-                                if is_valid_cielab(&candidate_c) {
-                                    let cost = cost(&input_c1, &input_c2, input_i, &candidate_c);
-
-                                    if cost < min_cost {
-                                        min_cost = cost;
-                                        min_cost_oc = candidate_c;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    for pl in 0..width {
-                        let l = min_l + pl as f64 * increment;
+            println!("Resuming from sample {}", checkpoint.sample_idx);
+
+            (seed, checkpoint.rng, sampler, checkpoint.sample_idx, checkpoint.bytes_written, output)
+        },
+        None => {
+            let seed = seed_setting(config, args);
+            let rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+            let mut output = File::create(data_path(dir, seed, &encoding)).unwrap();
+            let header = encoding.header(&columns);
+            output.write_all(&header).unwrap();
+
+            (seed, rng, Sampler::new(sampling.clone(), seed), 0, header.len() as u64, output)
+        },
+    };
+    println!("Seed: {}", seed);
 
-                        for pa in 0..width {
-                            let a = min_a + pa as f64 * increment;
+    let save_checkpoint = |rng: &rand_xoshiro::Xoshiro256PlusPlus, sampler: &Sampler, sample_idx: i32, bytes_written: u64| {
+        Checkpoint {
+            seed,
+            sample_count,
+            iteration_count,
+            min_width,
+            sampling: sampling.clone(),
+            augmentation,
+            sequence,
+            encoding,
+            color_encoding,
+            active_learning: active_learning.clone(),
+            sample_idx,
+            bytes_written,
+            rng: rng.clone(),
+            sampler_state: sampler.state(),
+        }.save(dir);
+    };
 
-                            for pb in 0..width {
-                                let b = min_b + pb as f64 * increment;
+    println!("Generating samples...");
 
-                                let candidate_c = [l, a, b];
+    let log = if progress_log { Some(Progress::open_log(dir, seed, resume_seed.is_some())) } else { None };
+    let mut progress = Progress::new(sample_count, progress_interval, sample_idx, log);
 
-                                let cost = cost(&input_c1, &input_c2, input_i, &candidate_c);
+    let selector = active_learning.clone().map(Selector::new);
+    let mut symmetry_check = SymmetryCheck::new(verify_tolerance);
+    let mut buffer = Vec::new();
+    'outer_loop:
+    while sample_idx < sample_count {
+        let (input_c1, input_c2, input_i) = match &selector {
+            Some(selector) => selector.select(&mut sampler, &mut rng),
+            None => sampler.sample(&mut rng),
+        };
 
-                                if cost < min_cost {
-                                    min_cost = cost;
-                                    min_cost_oc = candidate_c;
-                                }
-                            }
-                        }
+        let mut solved = Vec::new();
+        match sequence {
+            // Inputs chosen by active learning are retried rather than dropped, like the ones of sequences.
+            None => match (0..if selector.is_some() { MAX_ATTEMPTS } else { 1 }).find_map(|_| progress.record(solve(&input_c1, &input_c2, input_i, iteration_count, min_width, &mut rng))) {
+                Some(output_c) => solved.push((input_i, output_c)),
+                None => continue 'outer_loop,
+            },
+            Some(sequence) => {
+                for input_i in sequence.interpolants(&mut rng) {
+                    match (0..MAX_ATTEMPTS).find_map(|_| progress.record(solve(&input_c1, &input_c2, input_i, iteration_count, min_width, &mut rng))) {
+                        Some(output_c) => solved.push((input_i, output_c)),
+                        None => continue 'outer_loop,
                     }
                 }
+            },
+        }
 
-                span *= 0.5;
-                increment = span / width as f64;
-                min_l = min_cost_oc[0] - span / 2.0;
-                min_a = min_cost_oc[1] - span / 2.0;
-                min_b = min_cost_oc[2] - span / 2.0;
-            }
+        sampler.accept();
 
-            let output_c = min_cost_oc;
+        for (input_i, output_c) in solved.iter() {
+            if augmentation.verify_fraction > 0.0 && rng.gen::<f64>() < augmentation.verify_fraction {
+                if let Some(mirrored_output_c) = solve(&input_c2, &input_c1, 1.0 - input_i, iteration_count, min_width, &mut rng) {
+                    symmetry_check.add(cielab_dist_ciede2000(output_c, &mirrored_output_c));
+                }
+            }
+        }
 
-            if !is_valid_cielab(&output_c) {
-                continue 'outer_loop;
+        // The mirrored rows share the pair id of the gradient, so that they stay together with it.
+        let pair_id = sequence.map(|_| sample_idx);
+        for (input_i, output_c) in solved.iter() {
+            encoding.encode_row(&row(pair_id, &input_c1, &input_c2, *input_i, output_c, color_encoding), pair_id.is_some(), &mut buffer);
+        }
+        if augmentation.mirror {
+            for (input_i, output_c) in solved.iter().rev() {
+                encoding.encode_row(&row(pair_id, &input_c2, &input_c1, 1.0 - input_i, output_c, color_encoding), pair_id.is_some(), &mut buffer);
             }
+        }
 
-            {
-                let de1 = cielab_dist_ciede2000(&input_c1, &output_c);
-                let de2 = cielab_dist_ciede2000(&input_c2, &output_c);
-                let output_i = de1 / (de1 + de2);
-                let (min_i, max_i) = if input_i >= output_i {
-                    (output_i, input_i)
-                } else {
-                    (input_i, output_i)
-                };
-                if max_i / min_i > 1.001 {
-                    continue 'outer_loop;
-                }
+        sample_idx += 1;
+        progress.update(sample_idx);
+
+        if sample_idx % 1000 == 0 {
+            output.write_all(&buffer).unwrap();
+            output.flush().unwrap();
+            bytes_written += buffer.len() as u64;
+            if interrupt_at == Some(sample_idx) {
+                return;
             }
+            save_checkpoint(&rng, &sampler, sample_idx, bytes_written);
+            buffer.clear();
+        }
+    }
+
+    if !buffer.is_empty() {
+        output.write_all(&buffer).unwrap();
+        output.flush().unwrap();
+        bytes_written += buffer.len() as u64;
+        save_checkpoint(&rng, &sampler, sample_idx, bytes_written);
+    }
+    progress.report(sample_idx);
 
-            let input_c1 = rgb_to_srgb(&cielab_to_rgb(&input_c1));
-            let input_c2 = rgb_to_srgb(&cielab_to_rgb(&input_c2));
-            let output_c = rgb_to_srgb(&cielab_to_rgb(&output_c));
+    println!("Duration: {:.2?}", progress.elapsed());
 
-            str += &format!("{}, {}, {}, {}, {}, {}, {}, {}, {}, {}\n", input_c1[0], input_c1[1], input_c1[2], input_c2[0], input_c2[1], input_c2[2], input_i, output_c[0], output_c[1], output_c[2]);
+    let data_path = data_path(dir, seed, &encoding);
+    let rows_per_sample = sequence.map_or(1, |sequence| sequence.length) * if augmentation.mirror { 2 } else { 1 };
+    Metadata {
+        tool: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        seed,
+        sample_count: sample_idx,
+        row_count: (sample_idx as usize * rows_per_sample) as u64,
+        columns,
+        color_encoding,
+        iteration_count,
+        min_width,
+        sampling,
+        augmentation,
+        sequence,
+        encoding,
+        active_learning,
+        operations: Vec::new(),
+        sha256: file_sha256(&data_path),
+    }.save(&data_path);
 
-            sample_idx += 1;
+    symmetry_check.report();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::env;
+    use std::path::PathBuf;
+    use super::*;
+
+    /// A quick configuration, most samples are anchors that don't need solving.
+    fn config() -> Ini {
+        let mut config = Ini::new();
+        let _ = config.read(fs::read_to_string("config.ini").unwrap());
+        for (section, key, value) in [
+            ("general", "sample_count", "2100"),
+            ("general", "seed", "3"),
+            ("quality", "iteration_count", "5"),
+            ("quality", "min_width", "6"),
+            ("sampling", "anchor_fraction", "0.995"),
+            ("progress", "interval", "1000"),
+        ] {
+            config.set(section, key, Some(value.to_string()));
+        }
+        config
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn resume(dir: &Path, config: &Ini) {
+        generate(dir, config, &["sample-generator".to_string(), "--resume".to_string(), "3".to_string()], None);
+    }
 
-            if sample_idx % 1000 == 0 {
-                write!(output, "{}", str).unwrap();
-                str = String::new();
-                println!("\tSamples so far: {} of {}", sample_idx, sample_count);
+    #[test]
+    fn seeds_are_integers_or_random() {
+        assert_eq!(parse_seed("42"), 42);
+        assert_eq!(seed_setting(&config(), &["sample-generator".to_string(), "--seed".to_string(), "7".to_string()]), 7);
+        assert_eq!(seed_setting(&config(), &[]), 3);
+        assert_ne!((0..4).map(|_| parse_seed("random")).collect::<HashSet<_>>().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The seed must be a non-negative integer or \"random\".")]
+    fn negative_seeds_are_rejected() {
+        parse_seed("-1");
+    }
+
+    #[test]
+    fn configurations_without_the_newer_settings_work() {
+        let dir = dir("sample_generator_old_config");
+        let mut config = Ini::new();
+        let _ = config.read("[GENERAL]\nsample_count = 3\nseed = 4\n\n[QUALITY]\niteration_count = 5\nmin_width = 6\n".to_string());
+        generate(&dir, &config, &[], None);
+
+        let dataset = dataset::Dataset::read(&dir.join("data_4.csv").to_string_lossy());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(dataset.names, column_names(false, ColorEncoding::Srgb));
+        assert_eq!(dataset.len(), 3);
+    }
+
+    #[test]
+    fn near_endpoint_samples_cover_the_whole_width() {
+        let dir = dir("sample_generator_near_endpoint");
+        let mut config = config();
+        for (section, key, value) in [
+            ("general", "sample_count", "100"),
+            ("quality", "iteration_count", "7"),
+            ("quality", "min_width", "19"),
+            ("sampling", "anchor_fraction", "0.0"),
+            ("sampling", "near_endpoint_fraction", "1.0"),
+            ("sampling", "near_endpoint_width", "0.05"),
+        ] {
+            config.set(section, key, Some(value.to_string()));
+        }
+        generate(&dir, &config, &[], None);
+
+        let dataset = dataset::Dataset::read(&dir.join("data_3.csv").to_string_lossy());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let interpolants = dataset.rows().map(|row| dataset.interpolant(row)).collect::<Vec<_>>();
+        for (side, offsets) in [
+            ("start", interpolants.iter().filter(|&&t| t < 0.5).copied().collect::<Vec<_>>()),
+            ("end", interpolants.iter().filter(|&&t| t > 0.5).map(|t| 1.0 - t).collect()),
+        ] {
+            assert!(offsets.iter().all(|&offset| offset <= 0.05));
+            // Every fifth of the width, including the one closest to the endpoint, gets its share of the samples.
+            for bin in 0..5 {
+                let count = offsets.iter().filter(|&&offset| (offset / 0.01) as usize == bin).count();
+                assert!(count >= 3, "{} samples within {} and {} of the {}", count, bin as f64 * 0.01, (bin + 1) as f64 * 0.01, side);
             }
         }
+    }
 
-    if !str.is_empty() {
-        println!("\tSamples so far: {} of {}", sample_count, sample_count);
-        write!(output, "{}", str).unwrap();
+    #[test]
+    fn resumed_runs_match_uninterrupted_ones() {
+        let (uninterrupted, interrupted) = (dir("sample_generator_uninterrupted"), dir("sample_generator_interrupted"));
+        generate(&uninterrupted, &config(), &[], None);
+        // The rows of the second thousand samples are written but not checkpointed, resuming discards them.
+        generate(&interrupted, &config(), &[], Some(2000));
+        resume(&interrupted, &config());
+
+        let expected = fs::read(uninterrupted.join("data_3.csv")).unwrap();
+        let resumed = fs::read(interrupted.join("data_3.csv")).unwrap();
+        fs::remove_dir_all(&uninterrupted).unwrap();
+        fs::remove_dir_all(&interrupted).unwrap();
+
+        assert_eq!(expected.iter().filter(|&&b| b == b'\n').count(), 2101);
+        assert!(resumed == expected);
     }
 
-    let elapsed = instant.elapsed();
-    println!("Duration: {:.2?}", elapsed);
+    #[test]
+    #[should_panic(expected = "The quality configuration differs from the one used by the run being resumed.")]
+    fn resuming_with_another_configuration_is_rejected() {
+        let dir = dir("sample_generator_changed_config");
+        generate(&dir, &config(), &[], Some(2000));
+
+        let mut changed = config();
+        changed.set("quality", "min_width", Some("3".to_string()));
+        resume(&dir, &changed);
+    }
 }
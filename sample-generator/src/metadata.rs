@@ -0,0 +1,170 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::active::ActiveLearning;
+use crate::augmentation::Augmentation;
+use crate::color::*;
+use crate::dataset::{Dataset, Encoding};
+use crate::sampler::Sampling;
+use crate::sequence::Sequence;
+
+/// Provenance of a dataset, stored next to it in "[dataset file].meta.json".
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub tool: String,
+    pub version: String,
+    pub seed: u64,
+    pub sample_count: i32,
+    pub row_count: u64,
+    pub columns: Vec<String>,
+    /// Color space of the endpoints and the output.
+    pub color_encoding: ColorEncoding,
+    pub iteration_count: i32,
+    pub min_width: i32,
+    pub sampling: Sampling,
+    pub augmentation: Augmentation,
+    pub sequence: Option<Sequence>,
+    pub encoding: Encoding,
+    #[serde(default)]
+    pub active_learning: Option<ActiveLearning>,
+    /// The commands that derived the dataset from the generated one, in order.
+    #[serde(default)]
+    pub operations: Vec<String>,
+    pub sha256: String,
+}
+
+impl Metadata {
+    pub fn path(data_path: &str) -> String {
+        format!("{}.meta.json", data_path)
+    }
+
+    pub fn load(data_path: &str) -> Option<Metadata> {
+        let json = fs::read_to_string(Metadata::path(data_path)).ok()?;
+        Some(serde_json::from_str(&json).expect("Found invalid dataset metadata."))
+    }
+
+    pub fn save(&self, data_path: &str) {
+        fs::write(Metadata::path(data_path), serde_json::to_string_pretty(self).unwrap()).unwrap();
+    }
+
+    /// Metadata of a dataset without known provenance, e.g. a CSV file written by another tool, which only describes its
+    /// content. Binary datasets and CSV files without a header need it for their column names.
+    fn unknown(names: &[String]) -> Metadata {
+        Metadata {
+            tool: "unknown".to_string(),
+            version: "unknown".to_string(),
+            seed: 0,
+            sample_count: 0,
+            row_count: 0,
+            columns: names.to_vec(),
+            color_encoding: ColorEncoding::of_columns(names),
+            iteration_count: 0,
+            min_width: 0,
+            sampling: Sampling::default(),
+            augmentation: Augmentation::default(),
+            sequence: None,
+            encoding: Encoding::default(),
+            active_learning: None,
+            operations: Vec::new(),
+            sha256: String::new(),
+        }
+    }
+
+    /// Saves the metadata of `data_path`, which `operation` derived from `source`: the provenance of `source` with the
+    /// operation added. Without provenance to carry over, the metadata only describes the content, so that the column
+    /// names are never lost.
+    pub fn save_derived(source: &str, data_path: &str, operation: &str, dataset: &Dataset, encoding: Encoding) {
+        let mut metadata = Metadata::load(source).unwrap_or_else(|| Metadata::unknown(&dataset.names));
+
+        metadata.sample_count = dataset.groups().len() as i32;
+        metadata.row_count = dataset.len() as u64;
+        metadata.encoding = encoding;
+        metadata.operations.push(operation.to_string());
+        metadata.sha256 = file_sha256(data_path);
+        metadata.save(data_path);
+    }
+}
+
+/// Color spaces the endpoints and the output of a sample are written in. `All` writes every color in all of them.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorEncoding {
+    #[default]
+    Srgb,
+    LinearRgb,
+    Lab,
+    All,
+}
+
+impl FromStr for ColorEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srgb" => Ok(ColorEncoding::Srgb),
+            "linear_rgb" => Ok(ColorEncoding::LinearRgb),
+            "lab" => Ok(ColorEncoding::Lab),
+            "all" => Ok(ColorEncoding::All),
+            _ => Err(format!("Unknown color encoding \"{}\".", s)),
+        }
+    }
+}
+
+impl ColorEncoding {
+    /// Column name suffixes of the channels, in the order they are written.
+    pub fn channels(&self) -> Vec<&'static str> {
+        match self {
+            ColorEncoding::Srgb => vec!["r", "g", "b"],
+            ColorEncoding::LinearRgb => vec!["lin_r", "lin_g", "lin_b"],
+            ColorEncoding::Lab => vec!["lab_l", "lab_a", "lab_b"],
+            ColorEncoding::All => [ColorEncoding::Srgb, ColorEncoding::LinearRgb, ColorEncoding::Lab]
+                .iter()
+                .flat_map(|encoding| encoding.channels())
+                .collect(),
+        }
+    }
+
+    /// The encoding of a dataset with these columns, the one with the most channels of the output present.
+    pub fn of_columns(names: &[String]) -> ColorEncoding {
+        [ColorEncoding::All, ColorEncoding::Lab, ColorEncoding::LinearRgb, ColorEncoding::Srgb]
+            .into_iter()
+            .find(|encoding| encoding.channels().iter().all(|channel| names.contains(&format!("out_{}", channel))))
+            .unwrap_or_default()
+    }
+
+    pub fn encode(&self, c: &[f64; 3]) -> Vec<f64> {
+        match self {
+            ColorEncoding::Srgb => rgb_to_srgb(&cielab_to_rgb(c)).to_vec(),
+            ColorEncoding::LinearRgb => cielab_to_rgb(c).to_vec(),
+            ColorEncoding::Lab => c.to_vec(),
+            ColorEncoding::All => [ColorEncoding::Srgb, ColorEncoding::LinearRgb, ColorEncoding::Lab]
+                .iter()
+                .flat_map(|encoding| encoding.encode(c))
+                .collect(),
+        }
+    }
+}
+
+/// Columns of the rows written by `generate`. The endpoints and the output are written in every space of the color
+/// encoding, e.g. "c1_r", "c1_lin_r" and "c1_lab_l".
+pub fn column_names(sequence: bool, color_encoding: ColorEncoding) -> Vec<String> {
+    let color = |prefix: &str| color_encoding.channels().into_iter().map(|channel| format!("{}_{}", prefix, channel)).collect::<Vec<_>>();
+    let pair_id = if sequence { Some("pair_id".to_string()) } else { None };
+
+    pair_id
+        .into_iter()
+        .chain(color("c1"))
+        .chain(color("c2"))
+        .chain(Some("t".to_string()))
+        .chain(color("out"))
+        .collect()
+}
+
+pub fn file_sha256(path: &str) -> String {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path).unwrap(), &mut hasher).unwrap();
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,147 @@
+/// A k-d tree over the inputs of the samples for nearest neighbour queries.
+pub struct KdTree {
+    points: Vec<[f64; 7]>,
+    order: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(points: Vec<[f64; 7]>) -> KdTree {
+        let mut order = (0..points.len()).collect::<Vec<_>>();
+        build(&points, &mut order, 0);
+        KdTree { points, order }
+    }
+
+    /// The indices of the `k` points closest to point `idx`, excluding itself, nearest first.
+    pub fn nearest(&self, idx: usize, k: usize) -> Vec<usize> {
+        let mut best = Vec::with_capacity(k + 1);
+        self.search(&self.order, 0, idx, k, &mut best);
+        best.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// The indices of the points within `radius` of point `idx`, excluding itself, in index order.
+    pub fn within(&self, idx: usize, radius: f64) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.search_within(&self.order, 0, idx, radius.powi(2), &mut found);
+        found.sort_unstable();
+        found
+    }
+
+    fn squared_distance(&self, a: usize, b: usize) -> f64 {
+        self.points[a].iter().zip(self.points[b].iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>()
+    }
+
+    fn search(&self, order: &[usize], depth: usize, target: usize, k: usize, best: &mut Vec<(f64, usize)>) {
+        if order.is_empty() {
+            return;
+        }
+
+        let mid = order.len() / 2;
+        let node = order[mid];
+        if node != target {
+            let distance = self.squared_distance(node, target);
+            if best.len() < k || distance < best[best.len() - 1].0 {
+                let position = best.partition_point(|&(d, _)| d <= distance);
+                best.insert(position, (distance, node));
+                best.truncate(k);
+            }
+        }
+
+        let axis = depth % 7;
+        let diff = self.points[target][axis] - self.points[node][axis];
+        let (near, far) = if diff < 0.0 {
+            (&order[..mid], &order[mid + 1..])
+        } else {
+            (&order[mid + 1..], &order[..mid])
+        };
+
+        self.search(near, depth + 1, target, k, best);
+        if best.len() < k || diff.powi(2) < best[best.len() - 1].0 {
+            self.search(far, depth + 1, target, k, best);
+        }
+    }
+
+    fn search_within(&self, order: &[usize], depth: usize, target: usize, squared_radius: f64, found: &mut Vec<usize>) {
+        if order.is_empty() {
+            return;
+        }
+
+        let mid = order.len() / 2;
+        let node = order[mid];
+        if node != target && self.squared_distance(node, target) <= squared_radius {
+            found.push(node);
+        }
+
+        let axis = depth % 7;
+        let diff = self.points[target][axis] - self.points[node][axis];
+        let (near, far) = if diff < 0.0 {
+            (&order[..mid], &order[mid + 1..])
+        } else {
+            (&order[mid + 1..], &order[..mid])
+        };
+
+        self.search_within(near, depth + 1, target, squared_radius, found);
+        if diff.powi(2) <= squared_radius {
+            self.search_within(far, depth + 1, target, squared_radius, found);
+        }
+    }
+}
+
+fn build(points: &[[f64; 7]], order: &mut [usize], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+
+    let mid = order.len() / 2;
+    let axis = depth % 7;
+    order.select_nth_unstable_by(mid, |a, b| points[*a][axis].partial_cmp(&points[*b][axis]).unwrap());
+
+    let (left, right) = order.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    fn points() -> Vec<[f64; 7]> {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3);
+        (0..500).map(|_| [(); 7].map(|_| rng.gen())).collect()
+    }
+
+    fn brute_force_distances(points: &[[f64; 7]], idx: usize) -> Vec<(f64, usize)> {
+        let mut distances = (0..points.len())
+            .filter(|&other| other != idx)
+            .map(|other| (points[other].iter().zip(points[idx].iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>(), other))
+            .collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+        distances
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let points = points();
+        let tree = KdTree::new(points.clone());
+        for idx in (0..points.len()).step_by(7) {
+            let expected = brute_force_distances(&points, idx).into_iter().take(8).map(|(_, other)| other).collect::<Vec<_>>();
+            assert_eq!(tree.nearest(idx, 8), expected);
+        }
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let points = points();
+        let tree = KdTree::new(points.clone());
+        for idx in (0..points.len()).step_by(7) {
+            let mut expected = brute_force_distances(&points, idx)
+                .into_iter()
+                .filter(|&(distance, _)| distance <= 0.6_f64.powi(2))
+                .map(|(_, other)| other)
+                .collect::<Vec<_>>();
+            expected.sort_unstable();
+            assert!(!expected.is_empty());
+            assert_eq!(tree.within(idx, 0.6), expected);
+        }
+    }
+}
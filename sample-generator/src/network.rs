@@ -0,0 +1,61 @@
+use std::fs;
+use serde::Deserialize;
+use crate::metadata::ColorEncoding;
+
+/// A network in the "nn.json" layout written by nn: the weights of every node of every layer, each node starting with
+/// its bias weight, with sigmoid activations.
+#[derive(Deserialize)]
+pub struct Network {
+    layers: Vec<Vec<Vec<f64>>>,
+    num_inputs: u32,
+    /// Networks written before nn stored the encoding were trained on sRGB colors.
+    #[serde(default)]
+    color_encoding: ColorEncoding,
+}
+
+impl Network {
+    /// Rejects networks that weren't trained on colors in `color_encoding`.
+    pub fn load(path: &str, color_encoding: ColorEncoding) -> Network {
+        let json = fs::read_to_string(path).unwrap_or_else(|_| panic!("Couldn't read the file \"{}\"", path));
+        let network: Network = serde_json::from_str(&json).unwrap_or_else(|_| panic!("Found an invalid network in \"{}\".", path));
+        if network.color_encoding != color_encoding {
+            let name = |encoding: ColorEncoding| serde_json::to_string(&encoding).unwrap();
+            panic!("The network in \"{}\" was trained on {} colors but {} are needed.", path, name(network.color_encoding), name(color_encoding));
+        }
+        network
+    }
+
+    pub fn run(&self, inputs: &[f64]) -> Vec<f64> {
+        if inputs.len() != self.num_inputs as usize {
+            panic!("The input has a different length than the network's input layer.");
+        }
+
+        let mut values = inputs.to_vec();
+        for layer in self.layers.iter() {
+            values = layer
+                .iter()
+                .map(|node| {
+                    let sum = node[0] + node[1..].iter().zip(values.iter()).map(|(w, v)| w * v).sum::<f64>();
+                    1.0 / (1.0 + (-sum).exp())
+                })
+                .collect();
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks_without_an_encoding_are_srgb() {
+        Network::load("../image-generator/sample_nn.json", ColorEncoding::Srgb);
+    }
+
+    #[test]
+    #[should_panic(expected = "was trained on \"srgb\" colors but \"lab\" are needed")]
+    fn networks_of_other_encodings_are_rejected() {
+        Network::load("../image-generator/sample_nn.json", ColorEncoding::Lab);
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use rand::SeedableRng;
+use crate::color::*;
+use crate::dataset::{Dataset, Encoding, Format, Precision};
+use crate::metadata::Metadata;
+use crate::neighbours::KdTree;
+use crate::parse_seed;
+use crate::sequence::MAX_ATTEMPTS;
+use crate::solver::solve;
+
+/// Disagreement in CIEDE2000 per unit of distance in sRGB and the interpolant. Clean datasets stay well below it.
+const DEFAULT_THRESHOLD: f64 = 100.0;
+
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .map(|idx| args.get(idx + 1).unwrap_or_else(|| panic!("\"{}\" must be followed by a value.", name)).as_str())
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values[values.len() / 2]
+}
+
+/// How far the output of a row is from the straight CIELAB interpolation of its endpoints.
+fn residual(dataset: &Dataset, row: &[f64]) -> [f64; 3] {
+    let c1 = dataset.cielab(row, "c1");
+    let c2 = dataset.cielab(row, "c2");
+    let t = dataset.interpolant(row);
+    let output = dataset.cielab(row, "out");
+    [0, 1, 2].map(|k| output[k] - (c1[k] + t * (c2[k] - c1[k])))
+}
+
+/// Whether `b` is the mirror of `a`, the same gradient with the endpoints swapped.
+fn is_mirror(dataset: &Dataset, a: &[f64], b: &[f64]) -> bool {
+    dataset.cielab(a, "c1") == dataset.cielab(b, "c2")
+        && dataset.cielab(a, "c2") == dataset.cielab(b, "c1")
+        && (dataset.interpolant(a) + dataset.interpolant(b) - 1.0).abs() < 1e-6
+}
+
+/// `sample-generator outliers <input> <output> [--neighbours k | --radius r] [--threshold distance] [--resolve iteration_count,min_width] [--seed seed]`
+///
+/// Every neighbour of a sample in input space, one of its `k` nearest or one within a distance of `r` in sRGB and the
+/// interpolant, predicts its output as the straight CIELAB interpolation of its endpoints, moved by as much as the
+/// neighbour's own output is moved from its interpolation. The disagreement of the sample is the median CIEDE2000
+/// distance to these predictions per unit of input distance to the neighbour, so that distant neighbours in sparse
+/// datasets count less. Rows of the same group, the other rows of a gradient and mirrors, are no neighbours. Samples
+/// that disagree by more than the threshold are listed in "[input].outliers.csv". The output dataset leaves out their
+/// groups or, with `--resolve`, contains them solved again with the given quality, mirrors taking the new output of
+/// their original.
+pub fn outliers(args: &[String]) {
+    if args.len() < 2 {
+        panic!("Usage: outliers <input> <output> [--neighbours k | --radius r] [--threshold distance] [--resolve iteration_count,min_width] [--seed seed]");
+    }
+    let neighbour_count = flag_value(args, "--neighbours").map_or(8, |k| k.parse::<usize>().expect("Found an invalid neighbour count."));
+    let radius = flag_value(args, "--radius").map(|radius| radius.parse::<f64>().ok().filter(|&radius| radius > 0.0).expect("Found an invalid radius."));
+    let threshold = flag_value(args, "--threshold").map_or(DEFAULT_THRESHOLD, |threshold| threshold.parse::<f64>().expect("Found an invalid threshold."));
+    let resolve = flag_value(args, "--resolve").map(|quality| {
+        let quality = quality
+            .split(',')
+            .map(|v| v.trim().parse::<i32>().expect("\"--resolve\" must be followed by \"iteration_count,min_width\"."))
+            .collect::<Vec<_>>();
+        if quality.len() != 2 {
+            panic!("\"--resolve\" must be followed by \"iteration_count,min_width\".");
+        }
+        (quality[0], quality[1])
+    });
+    let seed = flag_value(args, "--seed").map_or(0, parse_seed);
+
+    let mut dataset = Dataset::read(&args[0]);
+
+    let groups = dataset.groups();
+    let mut group_of = vec![0; dataset.len()];
+    for (group_idx, group) in groups.iter().enumerate() {
+        group_of[group.clone()].fill(group_idx);
+    }
+
+    let points = dataset.rows().map(|row| dataset.input_point(row)).collect::<Vec<_>>();
+    let tree = KdTree::new(points.clone());
+    let residuals = dataset.rows().map(|row| residual(&dataset, row)).collect::<Vec<_>>();
+
+    let neighbours = (0..dataset.len())
+        .map(|idx| {
+            let other_group = |&neighbour: &usize| group_of[neighbour] != group_of[idx];
+            match radius {
+                Some(radius) => tree.within(idx, radius).into_iter().filter(other_group).collect::<Vec<_>>(),
+                None => {
+                    let group_len = groups[group_of[idx]].len();
+                    tree.nearest(idx, neighbour_count + group_len - 1).into_iter().filter(other_group).take(neighbour_count).collect()
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    let disagreements = (0..dataset.len())
+        .map(|idx| {
+            let row = dataset.row(idx);
+            let output = dataset.cielab(row, "out");
+            let interpolation = [0, 1, 2].map(|k| output[k] - residuals[idx][k]);
+            median(
+                neighbours[idx]
+                    .iter()
+                    .map(|&neighbour| {
+                        let prediction = [0, 1, 2].map(|k| interpolation[k] + residuals[neighbour][k]);
+                        let distance = points[idx].iter().zip(points[neighbour].iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>().sqrt();
+                        cielab_dist_ciede2000(&output, &prediction) / distance.max(f64::EPSILON)
+                    })
+                    .collect(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Samples without neighbours within the radius have nothing to disagree with.
+    let flagged = (0..dataset.len())
+        .filter(|&idx| !neighbours[idx].is_empty() && disagreements[idx] > threshold)
+        .map(|idx| (idx, disagreements[idx]))
+        .collect::<Vec<_>>();
+    let mut flagged_groups = flagged.iter().map(|&(idx, _)| group_of[idx]).collect::<Vec<_>>();
+    flagged_groups.dedup();
+
+    let list = "row, disagreement\n".to_string()
+        + &flagged.iter().map(|(idx, disagreement)| format!("{}, {}\n", idx, disagreement)).collect::<String>();
+    fs::write(format!("{}.outliers.csv", args[0]), list).unwrap();
+    println!("Flagged {} of {} samples, in {} of {} groups.", flagged.len(), dataset.len(), flagged_groups.len(), groups.len());
+
+    let mut removed = vec![false; dataset.len()];
+    match resolve {
+        Some((iteration_count, min_width)) => {
+            let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+            let mut resolved = 0;
+            for &group_idx in flagged_groups.iter() {
+                let group = groups[group_idx].clone();
+                let mut rows = group.clone().map(|idx| dataset.row(idx).to_vec()).collect::<Vec<_>>();
+
+                // A mirror takes the new output of its original, which is written before it, instead of being solved on its own.
+                let mut outputs: Vec<[f64; 3]> = Vec::with_capacity(rows.len());
+                for idx in 0..rows.len() {
+                    let output_c = match (0..idx).find(|&original| is_mirror(&dataset, &rows[original], &rows[idx])) {
+                        Some(original) => Some(outputs[original]),
+                        None => {
+                            let input_c1 = dataset.cielab(&rows[idx], "c1");
+                            let input_c2 = dataset.cielab(&rows[idx], "c2");
+                            let input_i = dataset.interpolant(&rows[idx]);
+                            (0..MAX_ATTEMPTS).find_map(|_| solve(&input_c1, &input_c2, input_i, iteration_count, min_width, &mut rng))
+                        },
+                    };
+                    match output_c {
+                        Some(output_c) => outputs.push(output_c),
+                        None => break,
+                    }
+                }
+                let solved = outputs.len() == rows.len();
+                for (row, output_c) in rows.iter_mut().zip(outputs.iter()) {
+                    dataset.set_cielab(row, "out", output_c);
+                }
+
+                if solved {
+                    let columns = dataset.columns;
+                    dataset.values[group.start * columns..group.end * columns].copy_from_slice(&rows.concat());
+                    resolved += 1;
+                } else {
+                    removed[group].fill(true);
+                }
+            }
+            println!("Solved {} groups again, removed {}.", resolved, flagged_groups.len() - resolved);
+        },
+        None => {
+            for &group_idx in flagged_groups.iter() {
+                removed[groups[group_idx].clone()].fill(true);
+            }
+        },
+    }
+
+    let encoding = Encoding {
+        format: Format::from_path(&args[1]),
+        precision: Precision::of(&args[0]),
+    };
+    let kept = Dataset::from_rows(
+        dataset.names.clone(),
+        dataset.rows().zip(removed.iter()).filter(|(_, &removed)| !removed).map(|(row, _)| row),
+    );
+    kept.write(&args[1], encoding);
+    Metadata::save_derived(&args[0], &args[1], &format!("outliers {}", args.join(" ")), &kept, encoding);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::Path;
+    use configparser::ini::Ini;
+    use super::*;
+
+    /// Generates a clean dataset with the default settings, a low quality and `overrides`, and returns its path.
+    fn generate(name: &str, overrides: &[(&str, &str, &str)]) -> String {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut config = Ini::new();
+        let _ = config.read(fs::read_to_string("config.ini").unwrap());
+        for &(section, key, value) in [
+            ("general", "seed", "8"),
+            ("quality", "iteration_count", "5"),
+            ("quality", "min_width", "6"),
+            ("progress", "interval", "1000"),
+        ]
+        .iter()
+        .chain(overrides)
+        {
+            config.set(section, key, Some(value.to_string()));
+        }
+        crate::generate(&dir, &config, &[], None);
+        dir.join("data_8.csv").to_string_lossy().to_string()
+    }
+
+    fn flagged_rows(input: &str) -> usize {
+        fs::read_to_string(format!("{}.outliers.csv", input)).unwrap().lines().count() - 1
+    }
+
+    #[test]
+    fn clean_datasets_have_almost_no_outliers() {
+        let input = generate("sample_generator_outliers_uniform", &[("general", "sample_count", "150")]);
+        let output = Path::new(&input).with_file_name("clean.csv").to_string_lossy().to_string();
+        outliers(&[input.clone(), output.clone()]);
+        assert!(flagged_rows(&input) <= 2, "{}", flagged_rows(&input));
+
+        let input = generate(
+            "sample_generator_outliers_sequences",
+            &[("general", "sample_count", "6"), ("sequences", "enabled", "true"), ("sequences", "length", "8"), ("augmentation", "mirror", "true")],
+        );
+        outliers(&[input.clone(), output.clone()]);
+        assert!(flagged_rows(&input) <= 2, "{}", flagged_rows(&input));
+    }
+
+    #[test]
+    fn whole_groups_are_removed_or_solved_again() {
+        let input = generate("sample_generator_outliers_groups", &[("general", "sample_count", "60"), ("augmentation", "mirror", "true")]);
+        let output = Path::new(&input).with_file_name("clean.csv").to_string_lossy().to_string();
+        let mut dataset = Dataset::read(&input);
+
+        // Move the output of an original row far away, its mirror keeps the right one.
+        let mut row = dataset.row(40).to_vec();
+        let output_c = dataset.cielab(&row, "out");
+        dataset.set_cielab(&mut row, "out", &[100.0 - output_c[0], -output_c[1], -output_c[2]]);
+        let columns = dataset.columns;
+        dataset.values[40 * columns..41 * columns].copy_from_slice(&row);
+        dataset.write(&input, Encoding::default());
+
+        outliers(&[input.clone(), output.clone()]);
+        let removed = Dataset::read(&output);
+        assert_eq!(removed.len(), dataset.len() - 2);
+        assert!(removed.rows().all(|row| row != dataset.row(41)));
+
+        outliers(&[input.clone(), output.clone(), "--resolve".to_string(), "5,6".to_string()]);
+        let resolved = Dataset::read(&output);
+        assert_eq!(resolved.len(), dataset.len());
+        assert_eq!(resolved.cielab(resolved.row(40), "out"), resolved.cielab(resolved.row(41), "out"));
+        assert!(cielab_dist_ciede2000(&resolved.cielab(resolved.row(40), "out"), &output_c) < 5.0);
+    }
+}
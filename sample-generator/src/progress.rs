@@ -0,0 +1,118 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// One line of the progress log.
+#[derive(Serialize)]
+struct Entry {
+    elapsed_seconds: f64,
+    samples: i32,
+    sample_count: i32,
+    samples_per_second: f64,
+    solve_count: u64,
+    rejection_rate: f64,
+    eta_seconds: Option<f64>,
+}
+
+/// Throughput, rejection rate and estimated time remaining of a generation run, printed every `interval` and
+/// optionally logged as JSON lines to "data_[seed].progress.jsonl".
+pub struct Progress {
+    sample_count: i32,
+    interval: Duration,
+    instant: Instant,
+    last_report: Instant,
+    /// Samples that were already written when the run was resumed, they don't count towards the throughput.
+    start_idx: i32,
+    solve_count: u64,
+    rejection_count: u64,
+    log: Option<File>,
+}
+
+impl Progress {
+    pub fn new(sample_count: i32, interval: Duration, start_idx: i32, log: Option<File>) -> Progress {
+        let instant = Instant::now();
+        Progress {
+            sample_count,
+            interval,
+            instant,
+            last_report: instant,
+            start_idx,
+            solve_count: 0,
+            rejection_count: 0,
+            log,
+        }
+    }
+
+    pub fn log_path(dir: &Path, seed: u64) -> String {
+        dir.join(format!("data_{}.progress.jsonl", seed)).to_string_lossy().to_string()
+    }
+
+    /// The log is appended to when resuming so that it covers the whole run.
+    pub fn open_log(dir: &Path, seed: u64, resume: bool) -> File {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(Progress::log_path(dir, seed))
+            .unwrap()
+    }
+
+    /// Counts a solver result of a sample.
+    pub fn record<T>(&mut self, result: Option<T>) -> Option<T> {
+        self.solve_count += 1;
+        if result.is_none() {
+            self.rejection_count += 1;
+        }
+        result
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+
+    /// Reports if `interval` has passed since the last report.
+    pub fn update(&mut self, sample_idx: i32) {
+        if self.last_report.elapsed() >= self.interval {
+            self.report(sample_idx);
+        }
+    }
+
+    pub fn report(&mut self, sample_idx: i32) {
+        self.last_report = Instant::now();
+
+        let elapsed = self.elapsed().as_secs_f64();
+        let samples_per_second = (sample_idx - self.start_idx) as f64 / elapsed.max(f64::EPSILON);
+        let rejection_rate = self.rejection_count as f64 / self.solve_count.max(1) as f64;
+        let eta_seconds = if samples_per_second > 0.0 {
+            Some((self.sample_count - sample_idx).max(0) as f64 / samples_per_second)
+        } else {
+            None
+        };
+
+        println!(
+            "\tSamples so far: {} of {} ({:.1}%), {:.1} samples/s, rejection rate {:.1}%, ETA {}",
+            sample_idx,
+            self.sample_count,
+            100.0 * sample_idx as f64 / self.sample_count.max(1) as f64,
+            samples_per_second,
+            100.0 * rejection_rate,
+            eta_seconds.map_or("unknown".to_string(), |eta| format!("{:.0?}", Duration::from_secs_f64(eta))),
+        );
+
+        if let Some(log) = &mut self.log {
+            let entry = Entry {
+                elapsed_seconds: elapsed,
+                samples: sample_idx,
+                sample_count: self.sample_count,
+                samples_per_second,
+                solve_count: self.solve_count,
+                rejection_rate,
+                eta_seconds,
+            };
+            writeln!(log, "{}", serde_json::to_string(&entry).unwrap()).unwrap();
+        }
+    }
+}
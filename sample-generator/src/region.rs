@@ -0,0 +1,136 @@
+use configparser::ini::Ini;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::color::*;
+use crate::settings::setting;
+
+/// Upper bound of the CIELAB chroma of the colors that are inside the sRGB gamut.
+pub const MAX_CHROMA: f64 = 133.81;
+
+/// Restricts the endpoints of the samples to a part of the color space.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    /// Ranges of CIELAB L, a and b.
+    Lab { min: [f64; 3], max: [f64; 3] },
+    /// Ranges of CIELCh L, C and h, h in degrees. A hue range with `min > max` wraps around 0.
+    Lch { min: [f64; 3], max: [f64; 3] },
+    /// CIELAB colors that are within a CIEDE2000 distance of `radius` from any of the anchors.
+    Anchors { colors: Vec<[f64; 3]>, radius: f64 },
+}
+
+impl Region {
+    pub fn from_config(config: &Ini) -> Option<Region> {
+        let kind = setting(config, "region", "kind", "none".to_string());
+
+        match kind.as_str() {
+            "none" => None,
+            "lab" => Some(Region::Lab {
+                min: CIELAB_MIN,
+                max: CIELAB_MAX,
+            }.with_ranges(config, ["l", "a", "b"])),
+            "lch" => Some(Region::Lch {
+                min: [0.0, 0.0, 0.0],
+                max: [100.0, MAX_CHROMA, 360.0],
+            }.with_ranges(config, ["l", "c", "h"])),
+            "anchors" => {
+                let colors = config.get("region", "anchors")
+                    .expect("The \"anchors\" region requires a list of anchor colors.")
+                    .split(';')
+                    .map(|color| {
+                        let c = parse_numbers(color);
+                        if c.len() != 3 {
+                            panic!("Found an invalid anchor color \"{}\".", color.trim());
+                        }
+                        [c[0], c[1], c[2]]
+                    })
+                    .collect();
+                let radius = config.getfloat("region", "radius").unwrap()
+                    .expect("The \"anchors\" region requires a radius.");
+
+                Some(Region::Anchors { colors, radius })
+            },
+            _ => panic!("Unknown region kind \"{}\".", kind),
+        }
+    }
+
+    fn with_ranges(mut self, config: &Ini, keys: [&str; 3]) -> Region {
+        if let Region::Lab { min, max } | Region::Lch { min, max } = &mut self {
+            for (idx, key) in keys.iter().enumerate() {
+                if let Some(range) = config.get("region", key) {
+                    let range = parse_numbers(&range);
+                    if range.len() != 2 || (range[0] > range[1] && *key != "h") {
+                        panic!("The region range \"{}\" must be given as \"min, max\".", key);
+                    }
+                    min[idx] = range[0];
+                    max[idx] = range[1];
+                }
+            }
+        }
+        self
+    }
+
+    pub fn contains(&self, c: &[f64; 3]) -> bool {
+        match self {
+            Region::Lab { min, max } => (0..3).all(|idx| c[idx] >= min[idx] && c[idx] <= max[idx]),
+            Region::Lch { min, max } => {
+                let lch = cielab_to_cielch(c);
+                let hue_in_range = if min[2] <= max[2] {
+                    lch[2] >= min[2] && lch[2] <= max[2]
+                } else {
+                    lch[2] >= min[2] || lch[2] <= max[2]
+                };
+
+                (0..2).all(|idx| lch[idx] >= min[idx] && lch[idx] <= max[idx]) && hue_in_range
+            },
+            Region::Anchors { colors, radius } => colors
+                .iter()
+                .any(|anchor| cielab_dist_ciede2000(anchor, c) <= *radius),
+        }
+    }
+
+    /// A CIELAB box to draw candidates from. Anchor regions pick one anchor at random and return a box around it that is
+    /// scaled by the largest CIEDE2000 chroma weighting factor, so that it covers every color within `radius`.
+    pub fn cielab_box(&self, rng: &mut impl Rng) -> ([f64; 3], [f64; 3]) {
+        let (min, max) = match self {
+            Region::Lab { min, max } => (*min, *max),
+            Region::Lch { min, max } => (
+                [min[0], -max[1], -max[1]],
+                [max[0], max[1], max[1]],
+            ),
+            Region::Anchors { colors, radius } => {
+                let anchor = colors[rng.gen_range(0..colors.len())];
+                let half_width = radius * (1.0 + 0.045 * MAX_CHROMA);
+                (
+                    [anchor[0] - half_width, anchor[1] - half_width, anchor[2] - half_width],
+                    [anchor[0] + half_width, anchor[1] + half_width, anchor[2] + half_width],
+                )
+            },
+        };
+
+        let mut clamped_min = [0.0; 3];
+        let mut clamped_max = [0.0; 3];
+        for idx in 0..3 {
+            clamped_min[idx] = min[idx].max(CIELAB_MIN[idx]);
+            clamped_max[idx] = max[idx].min(CIELAB_MAX[idx]);
+            if clamped_min[idx] > clamped_max[idx] {
+                panic!("The region doesn't contain any valid colors.");
+            }
+        }
+        (clamped_min, clamped_max)
+    }
+
+    /// A CIELCh box to draw candidates from, with the same hue wrapping as the `Lch` region.
+    pub fn cielch_box(&self) -> ([f64; 3], [f64; 3]) {
+        match self {
+            Region::Lch { min, max } => (*min, *max),
+            _ => ([0.0, 0.0, 0.0], [100.0, MAX_CHROMA, 360.0]),
+        }
+    }
+}
+
+fn parse_numbers(s: &str) -> Vec<f64> {
+    s.split(',')
+        .map(|n| n.trim().parse::<f64>().unwrap_or_else(|_| panic!("Found an invalid number \"{}\".", n.trim())))
+        .collect()
+}
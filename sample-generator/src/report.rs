@@ -0,0 +1,144 @@
+use std::fs;
+use crate::color::*;
+use crate::dataset::Dataset;
+use crate::region::MAX_CHROMA;
+
+/// A step between neighbouring samples of a gradient is a discontinuity if its CIEDE2000 distance is this many times
+/// larger than the one expected from the endpoint distance.
+const JUMP_FACTOR: f64 = 3.0;
+
+struct Histogram {
+    name: &'static str,
+    min: f64,
+    max: f64,
+    counts: Vec<usize>,
+    /// Per bin, the values of samples next to a discontinuity, only known for sequence datasets.
+    near_counts: Option<Vec<usize>>,
+}
+
+impl Histogram {
+    /// `values` are given with the index of their sample, `near` tells which samples are next to a discontinuity.
+    fn new(name: &'static str, min: f64, max: f64, bins: usize, values: impl Iterator<Item = (usize, f64)>, near: Option<&[bool]>) -> Histogram {
+        let mut counts = vec![0; bins];
+        let mut near_counts = near.map(|_| vec![0; bins]);
+        for (idx, v) in values {
+            let bin = ((v - min) / (max - min) * bins as f64).floor().clamp(0.0, (bins - 1) as f64) as usize;
+            counts[bin] += 1;
+            if let (Some(near), Some(near_counts)) = (near, near_counts.as_mut()) {
+                near_counts[bin] += near[idx] as usize;
+            }
+        }
+        Histogram { name, min, max, counts, near_counts }
+    }
+
+    fn bin_range(&self, idx: usize) -> (f64, f64) {
+        let width = (self.max - self.min) / self.counts.len() as f64;
+        (self.min + idx as f64 * width, self.min + (idx + 1) as f64 * width)
+    }
+
+    fn print(&self) {
+        let total = self.counts.iter().sum::<usize>().max(1);
+        let max_count = self.counts.iter().copied().max().unwrap_or(0).max(1);
+
+        println!("{}:", self.name);
+        for (idx, &count) in self.counts.iter().enumerate() {
+            let (start, end) = self.bin_range(idx);
+            println!(
+                "\t{:>8.2} - {:>8.2} {:>9} {:>6.2}% {}",
+                start, end, count, 100.0 * count as f64 / total as f64, "#".repeat(40 * count / max_count),
+            );
+        }
+    }
+
+    fn csv_rows(&self) -> String {
+        (0..self.counts.len())
+            .map(|idx| {
+                let (start, end) = self.bin_range(idx);
+                let near_fraction = self
+                    .near_counts
+                    .as_ref()
+                    .map_or(String::new(), |near_counts| (near_counts[idx] as f64 / self.counts[idx].max(1) as f64).to_string());
+                format!("{}, {}, {}, {}, {}\n", self.name, start, end, self.counts[idx], near_fraction)
+            })
+            .collect()
+    }
+}
+
+/// `sample-generator report <input> [bins]` prints how a dataset covers the color space and writes the histograms to
+/// "[input].report.csv", with the fraction of the samples of every bin that are next to a discontinuity for sequence
+/// datasets.
+pub fn report(args: &[String]) {
+    if args.is_empty() {
+        panic!("Usage: report <input> [bins]");
+    }
+    let bins = args.get(1).map_or(10, |bins| bins.parse::<usize>().expect("Found an invalid bin count."));
+    if bins == 0 {
+        panic!("Found an invalid bin count.");
+    }
+
+    let dataset = Dataset::read(&args[0]);
+
+    let mut endpoints = Vec::new();
+    let mut endpoint_distances = Vec::new();
+    let mut interpolants = Vec::new();
+    for row in dataset.rows() {
+        let c1 = dataset.cielab(row, "c1");
+        let c2 = dataset.cielab(row, "c2");
+
+        endpoints.push(cielab_to_cielch(&c1));
+        endpoints.push(cielab_to_cielch(&c2));
+        endpoint_distances.push(cielab_dist_ciede2000(&c1, &c2));
+        interpolants.push(dataset.interpolant(row));
+    }
+    let max_distance = endpoint_distances.iter().copied().fold(0.0, f64::max).ceil().max(1.0);
+    let near = if dataset.has_pair_id() { Some(near_discontinuity(&dataset)) } else { None };
+
+    // Every sample has two endpoints.
+    let endpoint_values = |channel: usize| endpoints.iter().enumerate().map(move |(idx, c)| (idx / 2, c[channel]));
+    let near = near.as_deref();
+    let histograms = [
+        Histogram::new("endpoint_lightness", 0.0, 100.0, bins, endpoint_values(0), near),
+        Histogram::new("endpoint_chroma", 0.0, MAX_CHROMA, bins, endpoint_values(1), near),
+        Histogram::new("endpoint_hue", 0.0, 360.0, bins, endpoint_values(2), near),
+        Histogram::new("interpolant", 0.0, 1.0, bins, interpolants.into_iter().enumerate(), near),
+        Histogram::new("endpoint_distance", 0.0, max_distance, bins, endpoint_distances.into_iter().enumerate(), near),
+    ];
+
+    println!("Samples: {}", dataset.len());
+    for histogram in histograms.iter() {
+        histogram.print();
+    }
+
+    match near {
+        Some(near) => {
+            let near_count = near.iter().filter(|&&near| near).count();
+            println!("Samples next to a discontinuity: {} ({:.2}%)", near_count, 100.0 * near_count as f64 / dataset.len().max(1) as f64);
+        },
+        None => println!("Samples next to a discontinuity: unknown, discontinuities can only be detected in sequence datasets"),
+    }
+
+    let csv = "histogram, bin_start, bin_end, count, discontinuity_fraction\n".to_string()
+        + &histograms.iter().map(|histogram| histogram.csv_rows()).collect::<String>();
+    fs::write(format!("{}.report.csv", args[0]), csv).unwrap();
+}
+
+/// Marks the samples on either side of the steps in a gradient that are much larger than its endpoint distance suggests.
+fn near_discontinuity(dataset: &Dataset) -> Vec<bool> {
+    let mut near = vec![false; dataset.len()];
+    for group in dataset.groups() {
+        let first = dataset.row(group.start);
+        let distance = cielab_dist_ciede2000(&dataset.cielab(first, "c1"), &dataset.cielab(first, "c2"));
+
+        for idx in group.start..group.end.saturating_sub(1) {
+            let (a, b) = (dataset.row(idx), dataset.row(idx + 1));
+            let step = cielab_dist_ciede2000(&dataset.cielab(a, "out"), &dataset.cielab(b, "out"));
+            let expected = distance * (dataset.interpolant(b) - dataset.interpolant(a)).abs();
+
+            if step > JUMP_FACTOR * expected.max(1.0) {
+                near[idx] = true;
+                near[idx + 1] = true;
+            }
+        }
+    }
+    near
+}
@@ -0,0 +1,420 @@
+use std::str::FromStr;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::color::*;
+use crate::region::{Region, MAX_CHROMA};
+
+const HALTON_BASES: [u64; 6] = [2, 3, 5, 7, 11, 13];
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointDistribution {
+    UniformLab,
+    UniformSrgb,
+    UniformLch,
+    Halton,
+}
+
+impl FromStr for EndpointDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform_lab" => Ok(EndpointDistribution::UniformLab),
+            "uniform_srgb" => Ok(EndpointDistribution::UniformSrgb),
+            "uniform_lch" => Ok(EndpointDistribution::UniformLch),
+            "halton" => Ok(EndpointDistribution::Halton),
+            _ => Err(format!("Unknown endpoint distribution \"{}\".", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterpolantDistribution {
+    Uniform,
+    Halton,
+}
+
+impl FromStr for InterpolantDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(InterpolantDistribution::Uniform),
+            "halton" => Ok(InterpolantDistribution::Halton),
+            _ => Err(format!("Unknown interpolant distribution \"{}\".", s)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Sampling {
+    pub endpoints: EndpointDistribution,
+    pub interpolant: InterpolantDistribution,
+    #[serde(default)]
+    pub region: Option<Region>,
+    /// Fraction of the samples at exactly t = 0 or t = 1.
+    #[serde(default)]
+    pub anchor_fraction: f64,
+    /// Fraction of the samples with t within `near_endpoint_width` of 0 or 1.
+    #[serde(default)]
+    pub near_endpoint_fraction: f64,
+    #[serde(default)]
+    pub near_endpoint_width: f64,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            endpoints: EndpointDistribution::UniformLab,
+            interpolant: InterpolantDistribution::Uniform,
+            region: None,
+            anchor_fraction: 0.0,
+            near_endpoint_fraction: 0.0,
+            near_endpoint_width: 0.0,
+        }
+    }
+}
+
+/// A Halton sequence, randomly shifted so that different seeds cover the space differently.
+struct Halton {
+    index: u64,
+    bases: &'static [u64],
+    offsets: Vec<f64>,
+}
+
+impl Halton {
+    fn new(bases: &'static [u64], rng: &mut impl Rng) -> Halton {
+        Halton {
+            index: 0,
+            bases,
+            offsets: bases.iter().map(|_| rng.gen()).collect(),
+        }
+    }
+
+    fn next(&mut self) -> Vec<f64> {
+        self.index += 1;
+        self.offsets
+            .iter()
+            .zip(self.bases.iter())
+            .map(|(offset, &base)| (radical_inverse(self.index, base) + offset).fract())
+            .collect()
+    }
+}
+
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let mut result = 0.0;
+    let mut factor = 1.0 / base as f64;
+    while index > 0 {
+        result += factor * (index % base) as f64;
+        index /= base;
+        factor /= base as f64;
+    }
+    result
+}
+
+/// Where on the gradient the interpolant of a sample is.
+#[derive(Clone, Copy)]
+enum EndpointClass {
+    Interior,
+    Anchor { at_start: bool },
+    NearEndpoint { at_start: bool },
+}
+
+pub struct Sampler {
+    sampling: Sampling,
+    /// One per endpoint, with different bases, so that each endpoint is rejected on its own like the uniform ones.
+    endpoint_sequences: [Halton; 2],
+    interpolant_sequence: Halton,
+    /// Kept until the sample is accepted, so that rejected samples are replaced by ones of the same class and the
+    /// configured fractions hold for the written samples.
+    endpoint_class: Option<EndpointClass>,
+}
+
+impl Sampler {
+    /// The sequence offsets are derived from the seed rather than drawn from the generation RNG so that they don't
+    /// change the samples of the uniform distributions and can be recreated when resuming.
+    pub fn new(sampling: Sampling, seed: u64) -> Sampler {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+        rng.long_jump();
+
+        Sampler {
+            sampling,
+            endpoint_sequences: [Halton::new(&HALTON_BASES[0..3], &mut rng), Halton::new(&HALTON_BASES[3..6], &mut rng)],
+            interpolant_sequence: Halton::new(&HALTON_BASES[0..1], &mut rng),
+            endpoint_class: None,
+        }
+    }
+
+    /// Position in the low-discrepancy sequences, saved in checkpoints.
+    pub fn state(&self) -> [u64; 3] {
+        [self.endpoint_sequences[0].index, self.endpoint_sequences[1].index, self.interpolant_sequence.index]
+    }
+
+    pub fn restore(&mut self, state: [u64; 3]) {
+        self.endpoint_sequences[0].index = state[0];
+        self.endpoint_sequences[1].index = state[1];
+        self.interpolant_sequence.index = state[2];
+    }
+
+    pub fn sample(&mut self, rng: &mut impl Rng) -> ([f64; 3], [f64; 3], f64) {
+        let (c1, c2) = match self.sampling.endpoints {
+            EndpointDistribution::Halton => (self.halton_endpoint(0, rng), self.halton_endpoint(1, rng)),
+            _ => (self.random_endpoint(rng), self.random_endpoint(rng)),
+        };
+
+        let i = match self.sampling.interpolant {
+            InterpolantDistribution::Uniform => rng.gen(),
+            InterpolantDistribution::Halton => self.interpolant_sequence.next()[0],
+        };
+
+        let endpoint_class = match self.endpoint_class {
+            Some(endpoint_class) => endpoint_class,
+            None => *self.endpoint_class.insert(self.random_endpoint_class(rng)),
+        };
+        let i = match endpoint_class {
+            EndpointClass::Interior => i,
+            EndpointClass::Anchor { at_start } => if at_start { 0.0 } else { 1.0 },
+            EndpointClass::NearEndpoint { at_start } => {
+                let offset = i * self.sampling.near_endpoint_width;
+                if at_start { offset } else { 1.0 - offset }
+            },
+        };
+
+        (c1, c2, i)
+    }
+
+    /// Called once a sample is written, the next one gets a new endpoint class.
+    pub fn accept(&mut self) {
+        self.endpoint_class = None;
+    }
+
+    fn random_endpoint_class(&self, rng: &mut impl Rng) -> EndpointClass {
+        let sampling = &self.sampling;
+        if sampling.anchor_fraction <= 0.0 && sampling.near_endpoint_fraction <= 0.0 {
+            return EndpointClass::Interior;
+        }
+
+        let choice = rng.gen::<f64>();
+        let at_start = rng.gen::<bool>();
+        if choice < sampling.anchor_fraction {
+            EndpointClass::Anchor { at_start }
+        } else if choice < sampling.anchor_fraction + sampling.near_endpoint_fraction {
+            EndpointClass::NearEndpoint { at_start }
+        } else {
+            EndpointClass::Interior
+        }
+    }
+
+    /// The next point of the sequence of endpoint `idx` that is valid and in the region, mapped into the CIELAB box
+    /// around the region like the uniform distributions draw from it, so that small regions don't reject almost every
+    /// point.
+    fn halton_endpoint(&mut self, idx: usize, rng: &mut impl Rng) -> [f64; 3] {
+        loop {
+            let point = self.endpoint_sequences[idx].next();
+            let (min, max) = match &self.sampling.region {
+                Some(region) => region.cielab_box(rng),
+                None => (CIELAB_MIN, CIELAB_MAX),
+            };
+            let c = [0, 1, 2].map(|k| min[k] + point[k] * (max[k] - min[k]));
+
+            if is_valid_cielab(&c) && self.in_region(&c) {
+                break c;
+            }
+        }
+    }
+
+    fn in_region(&self, c: &[f64; 3]) -> bool {
+        self.sampling.region.as_ref().is_none_or(|region| region.contains(c))
+    }
+
+    /// Uniform distributions in CIELAB and CIELCh draw their candidates from a box around the region, the other ones
+    /// are filtered.
+    fn random_endpoint(&self, rng: &mut impl Rng) -> [f64; 3] {
+        let region = match &self.sampling.region {
+            Some(region) => region,
+            None => return match self.sampling.endpoints {
+                EndpointDistribution::UniformLab => random_cielab(rng),
+                EndpointDistribution::UniformSrgb => random_srgb(rng),
+                EndpointDistribution::UniformLch => random_cielch(rng, &[0.0, 0.0, 0.0], &[100.0, MAX_CHROMA, 360.0]),
+                EndpointDistribution::Halton => unreachable!(),
+            },
+        };
+
+        loop {
+            let c = match self.sampling.endpoints {
+                EndpointDistribution::UniformLab => {
+                    let (min, max) = region.cielab_box(rng);
+                    random_cielab_in(rng, &min, &max)
+                },
+                EndpointDistribution::UniformSrgb => random_srgb(rng),
+                EndpointDistribution::UniformLch => {
+                    let (min, max) = region.cielch_box();
+                    random_cielch(rng, &min, &max)
+                },
+                EndpointDistribution::Halton => unreachable!(),
+            };
+
+            if region.contains(&c) {
+                break c;
+            }
+        }
+    }
+}
+
+fn random_cielab_in(rng: &mut impl Rng, min: &[f64; 3], max: &[f64; 3]) -> [f64; 3] {
+    loop {
+        let c = [
+            rng.gen_range(min[0]..=max[0]),
+            rng.gen_range(min[1]..=max[1]),
+            rng.gen_range(min[2]..=max[2]),
+        ];
+
+        if is_valid_cielab(&c) {
+            break c;
+        }
+    }
+}
+
+fn random_srgb(rng: &mut impl Rng) -> [f64; 3] {
+    rgb_to_cielab(&srgb_to_rgb(&[rng.gen(), rng.gen(), rng.gen()]))
+}
+
+/// A hue range with `min > max` wraps around 0.
+fn random_cielch(rng: &mut impl Rng, min: &[f64; 3], max: &[f64; 3]) -> [f64; 3] {
+    let hue_span = if min[2] <= max[2] {
+        max[2] - min[2]
+    } else {
+        max[2] + 360.0 - min[2]
+    };
+
+    loop {
+        let l = rng.gen_range(min[0]..=max[0]);
+        let chroma = rng.gen_range(min[1]..=max[1]);
+        let hue = (min[2] + rng.gen::<f64>() * hue_span).to_radians();
+        let c = [l, chroma * hue.cos(), chroma * hue.sin()];
+
+        if is_valid_cielab(&c) {
+            break c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radical_inverse_mirrors_the_digits() {
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(2, 2), 0.25);
+        assert_eq!(radical_inverse(3, 2), 0.75);
+        assert_eq!(radical_inverse(6, 2), 0.375);
+        assert!((radical_inverse(5, 3) - 7.0 / 9.0).abs() < 1e-12);
+    }
+
+    /// The first base^k points of every dimension fall evenly into base^m bins, whatever the random shift, which
+    /// uniform samples almost never do.
+    #[test]
+    fn halton_points_are_evenly_spread() {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(7);
+        let mut halton = Halton::new(&HALTON_BASES, &mut rng);
+        let points = (0..3usize.pow(6)).map(|_| halton.next()).collect::<Vec<_>>();
+
+        for (dimension, &base) in HALTON_BASES.iter().enumerate().take(2) {
+            let base = base as usize;
+            let point_count = if base == 2 { 512 } else { 729 };
+            let bin_count = base.pow(3);
+            let mut bins = vec![0usize; bin_count];
+            for point in points.iter().take(point_count) {
+                bins[(point[dimension] * bin_count as f64) as usize] += 1;
+            }
+            assert!(bins.iter().all(|&count| count == point_count / bin_count), "{:?}", bins);
+        }
+    }
+
+    #[test]
+    fn halton_sampling_is_deterministic_and_resumable() {
+        let sampling = Sampling {
+            endpoints: EndpointDistribution::Halton,
+            interpolant: InterpolantDistribution::Halton,
+            ..Sampling::default()
+        };
+        let samples = |sampler: &mut Sampler, count: usize| {
+            let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(1);
+            (0..count).map(|_| sampler.sample(&mut rng)).collect::<Vec<_>>()
+        };
+
+        let mut a = Sampler::new(sampling.clone(), 42);
+        let mut b = Sampler::new(sampling.clone(), 42);
+        let first = samples(&mut a, 100);
+        assert_eq!(first, samples(&mut b, 100));
+        assert_ne!(first, samples(&mut Sampler::new(sampling.clone(), 43), 100));
+
+        let mut resumed = Sampler::new(sampling, 42);
+        resumed.restore(a.state());
+        assert_eq!(samples(&mut a, 10), samples(&mut resumed, 10));
+    }
+
+    fn endpoints(sampling: Sampling, count: usize) -> Vec<[f64; 3]> {
+        let mut sampler = Sampler::new(sampling, 5);
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(5);
+        (0..count).flat_map(|_| {
+            let (c1, c2, _) = sampler.sample(&mut rng);
+            [c1, c2]
+        }).collect()
+    }
+
+    #[test]
+    fn uniform_srgb_endpoints_are_in_the_gamut() {
+        let sampling = Sampling { endpoints: EndpointDistribution::UniformSrgb, ..Sampling::default() };
+        for c in endpoints(sampling, 500) {
+            let rgb = cielab_to_rgb(&c);
+            assert!(rgb.iter().all(|v| (-1e-9..=1.0 + 1e-9).contains(v)), "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn uniform_lch_endpoints_are_in_the_gamut() {
+        let sampling = Sampling { endpoints: EndpointDistribution::UniformLch, ..Sampling::default() };
+        for c in endpoints(sampling, 500) {
+            assert!(is_valid_cielab(&c), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn lch_regions_wrap_around_the_hue() {
+        let region = Region::Lch { min: [20.0, 10.0, 330.0], max: [80.0, 40.0, 30.0] };
+        let sampling = Sampling { endpoints: EndpointDistribution::UniformLch, region: Some(region.clone()), ..Sampling::default() };
+        let hues = endpoints(sampling, 500)
+            .iter()
+            .map(|c| {
+                let lch = cielab_to_cielch(c);
+                assert!(region.contains(c) && (20.0..=80.0).contains(&lch[0]) && (10.0..=40.0).contains(&lch[1]), "{:?}", lch);
+                lch[2]
+            })
+            .collect::<Vec<_>>();
+
+        assert!(hues.iter().all(|&h| h >= 330.0 || h <= 30.0));
+        // Both sides of 0 are covered about equally.
+        let below_zero = hues.iter().filter(|&&h| h >= 330.0).count();
+        assert!((400..600).contains(&below_zero), "{}", below_zero);
+    }
+
+    #[test]
+    fn halton_endpoints_are_drawn_around_small_regions() {
+        let region = Region::Anchors { colors: vec![[65.0, 15.0, 20.0], [30.0, -20.0, -30.0]], radius: 3.0 };
+        let sampling = Sampling { endpoints: EndpointDistribution::Halton, region: Some(region.clone()), ..Sampling::default() };
+        let mut sampler = Sampler::new(sampling, 6);
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(6);
+        for _ in 0..100 {
+            let (c1, c2, _) = sampler.sample(&mut rng);
+            assert!(region.contains(&c1) && region.contains(&c2));
+        }
+
+        // Drawn from the box around an anchor, about one point in 400 is accepted, compared to one pair of points of the
+        // whole CIELAB box in a hundred million.
+        assert!(sampler.state()[0] + sampler.state()[1] < 200 * 1000, "{:?}", sampler.state());
+    }
+}
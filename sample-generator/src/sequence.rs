@@ -0,0 +1,55 @@
+use configparser::ini::Ini;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::settings::setting;
+
+/// Solver attempts per interpolant before the whole endpoint pair is dropped.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spacing {
+    Even,
+    Random,
+}
+
+/// Solves `length` interpolants per endpoint pair instead of one, so that whole gradients can be evaluated.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Sequence {
+    pub length: usize,
+    pub spacing: Spacing,
+}
+
+impl Sequence {
+    pub fn from_config(config: &Ini) -> Option<Sequence> {
+        if !setting(config, "sequences", "enabled", false) {
+            return None;
+        }
+
+        let length = setting(config, "sequences", "length", 16usize);
+        if length < 2 {
+            panic!("A sequence must contain at least 2 interpolants.");
+        }
+        let spacing = match setting(config, "sequences", "spacing", "even".to_string()).as_str() {
+            "even" => Spacing::Even,
+            "random" => Spacing::Random,
+            spacing => panic!("Unknown sequence spacing \"{}\".", spacing),
+        };
+
+        Some(Sequence { length, spacing })
+    }
+
+    /// Increasing interpolants in [0, 1). Evenly spaced ones are strictly between 0 and 1, random ones can be 0.
+    pub fn interpolants(&self, rng: &mut impl Rng) -> Vec<f64> {
+        match self.spacing {
+            Spacing::Even => (0..self.length)
+                .map(|idx| (idx as f64 + 0.5) / self.length as f64)
+                .collect(),
+            Spacing::Random => {
+                let mut interpolants = (0..self.length).map(|_| rng.gen()).collect::<Vec<f64>>();
+                interpolants.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                interpolants
+            },
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use std::str::FromStr;
+use configparser::ini::Ini;
+
+/// A value of config.ini, or `default` if it is missing or empty, so that configurations written before a setting was
+/// added keep working.
+pub fn setting<T: FromStr>(config: &Ini, section: &str, key: &str, default: T) -> T {
+    match config.get(section, key).filter(|value| !value.is_empty()) {
+        Some(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("Found an invalid value \"{}\" for \"{}\" in the \"{}\" section.", value, key, section)),
+        None => default,
+    }
+}
@@ -0,0 +1,114 @@
+use rand::Rng;
+use crate::color::*;
+
+pub fn cost(ic1: &[f64; 3], ic2: &[f64; 3], ii: f64, oc: &[f64; 3]) -> f64 {
+    let de1 = cielab_dist_ciede2000(ic1, oc);
+    let de2 = cielab_dist_ciede2000(ic2, oc);
+    let oi = de1 / (de1 + de2);
+
+    (de1 + de2) * ((oi - ii).abs() + 0.5)
+}
+
+/// Searches for the color of the gradient between `input_c1` and `input_c2` at `input_i` with a randomly offset grid
+/// that is refined `iteration_count` times. Returns `None` if the result isn't accurate enough to be used as a sample.
+/// The ends of the gradient are the endpoints themselves.
+pub fn solve(input_c1: &[f64; 3], input_c2: &[f64; 3], input_i: f64, iteration_count: i32, min_width: i32, rng: &mut impl Rng) -> Option<[f64; 3]> {
+    if input_i == 0.0 {
+        return Some(*input_c1);
+    }
+    if input_i == 1.0 {
+        return Some(*input_c2);
+    }
+
+    let output_c = search(input_c1, input_c2, input_i, iteration_count, min_width, rng);
+
+    if !is_valid_cielab(&output_c) {
+        return None;
+    }
+
+    {
+        let de1 = cielab_dist_ciede2000(input_c1, &output_c);
+        let de2 = cielab_dist_ciede2000(input_c2, &output_c);
+        let output_i = de1 / (de1 + de2);
+        let (min_i, max_i) = if input_i >= output_i {
+            (output_i, input_i)
+        } else {
+            (input_i, output_i)
+        };
+        // Relative to the interpolant in the middle, but absolute near the ends, where the grid can't get relatively
+        // as close and a relative check would reject almost every sample.
+        if max_i - min_i > 0.001 * min_i.max(0.5) {
+            return None;
+        }
+    }
+
+    Some(output_c)
+}
+
+/// The grid search of `solve` without the accuracy checks.
+pub fn search(input_c1: &[f64; 3], input_c2: &[f64; 3], input_i: f64, iteration_count: i32, min_width: i32, rng: &mut impl Rng) -> [f64; 3] {
+    let width = min_width + rng.gen_range(0..=5);
+    let mut span = 210.0 + 5.0 * rng.gen::<f64>();
+    let mut increment = span / width as f64;
+    let mut min_l = -54.5 + rng.gen::<f64>();
+    let mut min_a = -99.5 + rng.gen::<f64>();
+    let mut min_b = -109.5 + rng.gen::<f64>();
+    let mut min_cost = f64::INFINITY;
+    let mut min_cost_oc = [0.0; 3];
+    for iteration in 0..iteration_count {
+        if iteration == 0 {
+            for pl in 0..(width * 4) {
+                let l = min_l + (pl as f64 + 0.5) * (increment / 4.0);
+
+                for pa in 0..(width * 4) {
+                    let a = min_a + (pa as f64 + 0.5) * (increment / 4.0);
+
+                    for pb in 0..(width * 4) {
+                        let b = min_b + (pb as f64 + 0.5) * (increment / 4.0);
+
+                        let candidate_c = [l, a, b];
+
+                        if is_valid_cielab(&candidate_c) {
+                            let cost = cost(input_c1, input_c2, input_i, &candidate_c);
+
+                            if cost < min_cost {
+                                min_cost = cost;
+                                min_cost_oc = candidate_c;
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            for pl in 0..width {
+                let l = min_l + pl as f64 * increment;
+
+                for pa in 0..width {
+                    let a = min_a + pa as f64 * increment;
+
+                    for pb in 0..width {
+                        let b = min_b + pb as f64 * increment;
+
+                        let candidate_c = [l, a, b];
+
+                        let cost = cost(input_c1, input_c2, input_i, &candidate_c);
+
+                        if cost < min_cost {
+                            min_cost = cost;
+                            min_cost_oc = candidate_c;
+                        }
+                    }
+                }
+            }
+        }
+
+        span *= 0.5;
+        increment = span / width as f64;
+        min_l = min_cost_oc[0] - span / 2.0;
+        min_a = min_cost_oc[1] - span / 2.0;
+        min_b = min_cost_oc[2] - span / 2.0;
+    }
+
+    min_cost_oc
+}
+
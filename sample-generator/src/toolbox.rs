@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use crate::dataset::{Dataset, Encoding, Format, Precision};
+use crate::metadata::Metadata;
+use crate::neighbours::KdTree;
+use crate::parse_seed;
+
+/// Gradients of sequence datasets and mirrored rows are kept together by every command. Binary outputs keep the
+/// precision of `source`, whose metadata is carried over with `operation` recorded in it.
+fn write_groups(dataset: &Dataset, groups: &[Range<usize>], path: &str, source: &str, operation: &str) {
+    let rows = groups.iter().flat_map(|group| group.clone().map(|idx| dataset.row(idx)));
+    let encoding = Encoding {
+        format: Format::from_path(path),
+        precision: Precision::of(source),
+    };
+    let written = Dataset::from_rows(dataset.names.clone(), rows);
+    written.write(path, encoding);
+    Metadata::save_derived(source, path, operation, &written, encoding);
+}
+
+/// `sample-generator merge <output> <input>...` concatenates datasets, renumbering the gradients of sequence datasets.
+/// The merged dataset keeps the metadata and the precision of the first input.
+pub fn merge(args: &[String]) {
+    if args.len() < 2 {
+        panic!("Usage: merge <output> <input>...");
+    }
+
+    let mut merged: Option<Dataset> = None;
+    for path in &args[1..] {
+        let mut dataset = Dataset::read(path);
+        println!("{}: {} samples", path, dataset.len());
+
+        let merged = merged.get_or_insert_with(|| Dataset::from_rows(dataset.names.clone(), []));
+        if merged.names != dataset.names {
+            panic!("\"{}\" has the columns {:?} instead of {:?}.", path, dataset.names, merged.names);
+        }
+
+        if dataset.has_pair_id() {
+            let next_pair_id = merged.groups().len();
+            for (pair_id, group) in dataset.groups().into_iter().enumerate() {
+                for idx in group {
+                    dataset.values[idx * dataset.columns] = (next_pair_id + pair_id) as f64;
+                }
+            }
+        }
+        merged.values.extend(dataset.values);
+    }
+
+    let merged = merged.unwrap();
+    write_groups(&merged, &merged.groups(), &args[0], &args[1], &format!("merge {}", args.join(" ")));
+    println!("Merged {} samples.", merged.len());
+}
+
+/// `sample-generator dedupe <input> <output> [tolerance]` removes groups, a row or a gradient together with its mirror,
+/// whose inputs are equal to those of an earlier kept group or, with a tolerance, where every row is within that
+/// Euclidean distance of the corresponding row of an earlier kept group, in sRGB and the interpolant.
+pub fn dedupe(args: &[String]) {
+    if args.len() < 2 {
+        panic!("Usage: dedupe <input> <output> [tolerance]");
+    }
+    let tolerance = args.get(2).map_or(0.0, |tolerance| tolerance.parse::<f64>().expect("Found an invalid tolerance."));
+    if tolerance.is_nan() || tolerance < 0.0 {
+        panic!("The tolerance must not be negative.");
+    }
+
+    let dataset = Dataset::read(&args[0]);
+    let groups = dataset.groups();
+
+    let kept = if tolerance > 0.0 {
+        let points = dataset.rows().map(|row| dataset.input_point(row)).collect::<Vec<_>>();
+        let within_tolerance = |a: usize, b: usize| {
+            points[a].iter().zip(points[b].iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>() <= tolerance.powi(2)
+        };
+
+        // Candidates are found by the first rows of the groups.
+        let tree = KdTree::new(groups.iter().map(|group| points[group.start]).collect());
+        let mut kept = vec![false; groups.len()];
+        for (idx, group) in groups.iter().enumerate() {
+            kept[idx] = !tree.within(idx, tolerance).into_iter().any(|other| {
+                other < idx
+                    && kept[other]
+                    && groups[other].len() == group.len()
+                    && groups[other].clone().zip(group.clone()).all(|(a, b)| within_tolerance(a, b))
+            });
+        }
+        groups.into_iter().zip(kept).filter(|(_, kept)| *kept).map(|(group, _)| group).collect::<Vec<_>>()
+    } else {
+        let inputs = dataset.input_columns();
+        let mut seen = HashSet::new();
+        groups
+            .into_iter()
+            .filter(|group| seen.insert(group.clone().flat_map(|idx| dataset.row(idx)[inputs.clone()].iter().map(|v| v.to_bits())).collect::<Vec<_>>()))
+            .collect::<Vec<_>>()
+    };
+
+    let kept_len = kept.iter().map(|group| group.len()).sum::<usize>();
+    write_groups(&dataset, &kept, &args[1], &args[0], &format!("dedupe {}", args.join(" ")));
+    println!("Removed {} of {} samples.", dataset.len() - kept_len, dataset.len());
+}
+
+/// `sample-generator shuffle <input> <output> <seed>`
+pub fn shuffle(args: &[String]) {
+    if args.len() < 3 {
+        panic!("Usage: shuffle <input> <output> <seed>");
+    }
+    let seed = parse_seed(&args[2]);
+    println!("Seed: {}", seed);
+
+    let dataset = Dataset::read(&args[0]);
+    let mut groups = dataset.groups();
+    groups.shuffle(&mut rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed));
+
+    write_groups(&dataset, &groups, &args[1], &args[0], &format!("shuffle {} {} {}", args[0], args[1], seed));
+    println!("Shuffled {} samples.", dataset.len());
+}
+
+/// `sample-generator split <input> <name> <train> <validation> <test>` writes the given fractions of the dataset, in
+/// order, to "[name]_train", "[name]_validation" and "[name]_test" with the extension of the input.
+pub fn split(args: &[String]) {
+    if args.len() < 5 {
+        panic!("Usage: split <input> <name> <train> <validation> <test>");
+    }
+    let fractions = args[2..5]
+        .iter()
+        .map(|fraction| fraction.parse::<f64>().expect("Found an invalid fraction."))
+        .collect::<Vec<_>>();
+    let total = fractions.iter().sum::<f64>();
+    if fractions.iter().any(|&fraction| fraction < 0.0) || total <= 0.0 {
+        panic!("The fractions must be non-negative and not all zero.");
+    }
+
+    let dataset = Dataset::read(&args[0]);
+    let groups = dataset.groups();
+    let extension = Format::from_path(&args[0]).extension();
+
+    let mut start = 0;
+    let mut cumulative = 0.0;
+    for (fraction, split_name) in fractions.iter().zip(["train", "validation", "test"]) {
+        cumulative += fraction;
+        let end = ((cumulative / total) * groups.len() as f64).round() as usize;
+        let path = format!("{}_{}.{}", args[1], split_name, extension);
+
+        write_groups(&dataset, &groups[start..end], &path, &args[0], &format!("split {} ({})", args.join(" "), split_name));
+        println!("{}: {} samples", path, groups[start..end].iter().map(|group| group.len()).sum::<usize>());
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use super::*;
+    use crate::metadata::{column_names, ColorEncoding};
+
+    fn path(name: &str) -> String {
+        env::temp_dir().join(name).to_string_lossy().to_string()
+    }
+
+    fn dataset(endpoints: &[([f64; 3], [f64; 3], f64)]) -> Dataset {
+        let rows = endpoints
+            .iter()
+            .map(|(c1, c2, t)| c1.iter().chain(c2.iter()).chain([*t, 0.5, 0.5, 0.5].iter()).copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Dataset::from_rows(column_names(false, ColorEncoding::Srgb), rows.iter().map(Vec::as_slice))
+    }
+
+    #[test]
+    fn dedupe_removes_whole_groups() {
+        let (a, b, c) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7], [0.4, 0.4, 0.4]);
+        let (input, output) = (path("toolbox_dedupe_in.csv"), path("toolbox_dedupe_out.csv"));
+        dataset(&[(a, b, 0.25), (b, a, 0.75), (a, c, 0.5), (a, b, 0.25), (b, a, 0.75), (a, b, 0.25)]).write(&input, Encoding::default());
+
+        dedupe(&[input.clone(), output.clone()]);
+        let deduped = Dataset::read(&output);
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        // The last row isn't a duplicate of the first group, which includes the mirror.
+        assert_eq!(deduped.values, dataset(&[(a, b, 0.25), (b, a, 0.75), (a, c, 0.5), (a, b, 0.25)]).values);
+    }
+
+    #[test]
+    fn dedupe_removes_rows_within_the_tolerance() {
+        let (a, b) = ([0.1, 0.2, 0.3], [0.9, 0.8, 0.7]);
+        let (input, output) = (path("toolbox_tolerance_in.csv"), path("toolbox_tolerance_out.csv"));
+        // The first two rows round to different multiples of the tolerance, the third one is too far from both.
+        dataset(&[(a, b, 0.1049), (a, b, 0.1051), (a, b, 0.12)]).write(&input, Encoding::default());
+
+        dedupe(&[input.clone(), output.clone(), "0.01".to_string()]);
+        let deduped = Dataset::read(&output);
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert_eq!(deduped.values, dataset(&[(a, b, 0.1049), (a, b, 0.12)]).values);
+    }
+
+    #[test]
+    fn binary_outputs_keep_the_input_precision() {
+        let (input, output) = (path("toolbox_precision_in.bin"), path("toolbox_precision_out.bin"));
+        dataset(&[([0.1, 0.2, 0.3], [0.9, 0.8, 0.7], 0.25)]).write(&input, Encoding { format: Format::Binary, precision: Precision::F32 });
+
+        shuffle(&[input.clone(), output.clone(), "1".to_string()]);
+        let precision = Precision::of(&output);
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert_eq!(precision, Precision::F32);
+    }
+}